@@ -1,3 +1,9 @@
 use freertos::FreeRtosError;
 
 pub type Error = FreeRtosError;
+
+/// Error of operation that hasn't completed in time.
+#[doc(hidden)]
+pub fn timeout() -> Error {
+    FreeRtosError::Timeout
+}
@@ -0,0 +1,108 @@
+//! Kernel functions that aren't wrapped by `freertos-rust`.
+
+use core::ffi::{c_char, c_void};
+use freertos::{
+    FreeRtosBaseType, FreeRtosQueueHandle, FreeRtosTaskHandle, FreeRtosTickType, FreeRtosUBaseType,
+};
+
+pub const PD_TRUE: FreeRtosBaseType = 1;
+pub const PD_FALSE: FreeRtosBaseType = 0;
+
+pub const TASK_SCHEDULER_RUNNING: FreeRtosBaseType = 2;
+
+pub const QUEUE_TYPE_BASE: u8 = 0;
+pub const QUEUE_SEND_TO_BACK: FreeRtosBaseType = 0;
+
+/// `TimeOut_t`.
+#[repr(C)]
+pub struct TimeOut {
+    pub overflow_count: FreeRtosBaseType,
+    pub time_on_entering: FreeRtosTickType,
+}
+
+pub type EventGroupHandle = *mut c_void;
+pub type EventBits = FreeRtosTickType;
+
+extern "C" {
+    pub fn xTaskGetSchedulerState() -> FreeRtosBaseType;
+    pub fn pcTaskGetName(task: FreeRtosTaskHandle) -> *const c_char;
+    pub fn vTaskSuspend(task: FreeRtosTaskHandle);
+    pub fn vTaskSetThreadLocalStoragePointer(
+        task: FreeRtosTaskHandle,
+        index: FreeRtosBaseType,
+        value: *mut c_void,
+    );
+    pub fn pvTaskGetThreadLocalStoragePointer(
+        task: FreeRtosTaskHandle,
+        index: FreeRtosBaseType,
+    ) -> *mut c_void;
+    pub fn vTaskSetTimeOutState(timeout: *mut TimeOut);
+    pub fn xTaskGetTickCountFromISR() -> FreeRtosTickType;
+    pub fn vTaskDelayUntil(
+        previous_wake_time: *mut FreeRtosTickType,
+        time_increment: FreeRtosTickType,
+    );
+
+    pub fn xQueueGenericCreate(
+        length: FreeRtosUBaseType,
+        item_size: FreeRtosUBaseType,
+        queue_type: u8,
+    ) -> FreeRtosQueueHandle;
+    pub fn xQueueCreateCountingSemaphore(
+        max_count: FreeRtosUBaseType,
+        initial_count: FreeRtosUBaseType,
+    ) -> FreeRtosQueueHandle;
+    pub fn vQueueDelete(queue: FreeRtosQueueHandle);
+    pub fn uxQueueMessagesWaiting(queue: FreeRtosQueueHandle) -> FreeRtosUBaseType;
+    pub fn uxQueueMessagesWaitingFromISR(queue: FreeRtosQueueHandle) -> FreeRtosUBaseType;
+    pub fn xQueueGenericSend(
+        queue: FreeRtosQueueHandle,
+        item: *const c_void,
+        ticks_to_wait: FreeRtosTickType,
+        copy_position: FreeRtosBaseType,
+    ) -> FreeRtosBaseType;
+    pub fn xQueueGenericSendFromISR(
+        queue: FreeRtosQueueHandle,
+        item: *const c_void,
+        higher_priority_task_woken: *mut FreeRtosBaseType,
+        copy_position: FreeRtosBaseType,
+    ) -> FreeRtosBaseType;
+    pub fn xQueueReceive(
+        queue: FreeRtosQueueHandle,
+        buffer: *mut c_void,
+        ticks_to_wait: FreeRtosTickType,
+    ) -> FreeRtosBaseType;
+    pub fn xQueueReceiveFromISR(
+        queue: FreeRtosQueueHandle,
+        buffer: *mut c_void,
+        higher_priority_task_woken: *mut FreeRtosBaseType,
+    ) -> FreeRtosBaseType;
+
+    pub fn xEventGroupCreate() -> EventGroupHandle;
+    pub fn vEventGroupDelete(group: EventGroupHandle);
+    pub fn xEventGroupSetBits(group: EventGroupHandle, bits: EventBits) -> EventBits;
+    pub fn xEventGroupSetBitsFromISR(
+        group: EventGroupHandle,
+        bits: EventBits,
+        higher_priority_task_woken: *mut FreeRtosBaseType,
+    ) -> FreeRtosBaseType;
+    pub fn xEventGroupClearBits(group: EventGroupHandle, bits: EventBits) -> EventBits;
+    pub fn xEventGroupClearBitsFromISR(
+        group: EventGroupHandle,
+        bits: EventBits,
+    ) -> FreeRtosBaseType;
+    pub fn xEventGroupGetBitsFromISR(group: EventGroupHandle) -> EventBits;
+    pub fn xEventGroupWaitBits(
+        group: EventGroupHandle,
+        bits: EventBits,
+        clear_on_exit: FreeRtosBaseType,
+        wait_for_all: FreeRtosBaseType,
+        ticks_to_wait: FreeRtosTickType,
+    ) -> EventBits;
+    pub fn xEventGroupSync(
+        group: EventGroupHandle,
+        set: EventBits,
+        wait: EventBits,
+        ticks_to_wait: FreeRtosTickType,
+    ) -> EventBits;
+}
@@ -1,12 +1,12 @@
 #![no_std]
 
 mod alloc;
-mod macros;
-#[cfg(feature = "panic")]
-mod panic;
+mod ffi;
 
 pub mod error;
 pub mod io;
+pub mod panic;
+pub mod process;
 pub mod sync;
 pub mod task;
 pub mod test;
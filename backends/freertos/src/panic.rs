@@ -1,13 +1,140 @@
-use crate::println;
-use core::panic::PanicInfo;
+//! Panic handling.
+//!
+//! When `panic` feature is enabled the panic handler is provided by this module.
+//! When `hosted` feature is enabled the policy is applied through `std` panic hook instead,
+//! the hook is installed when the first task is spawned.
+
+use crate::{ffi, println, task::suspend_panicked};
+use core::{
+    cell::UnsafeCell,
+    ffi::CStr,
+    fmt::{self, Display},
+    panic::Location,
+    ptr::null,
+};
 
 extern "C" {
-    static __ustd_panicked: u8;
     fn __ustd_panic() -> !;
 }
 
+/// What to do when task panics.
+#[derive(Clone, Copy)]
+pub enum PanicPolicy {
+    /// Print panic info and abort the whole program.
+    ///
+    /// This is the default.
+    Abort,
+    /// Print panic info and suspend panicked task forever.
+    ///
+    /// [`JoinHandle::join`](crate::task::JoinHandle::join) of the task returns [`JoinError::Panicked`](crate::task::JoinError::Panicked).
+    ///
+    /// Suspended task is never deleted, so its stack and everything owned by it (including the task closure) are leaked.
+    Suspend,
+    /// Call hook with name of panicked task, panic message and location, then suspend the task as [`Self::Suspend`] does.
+    Hook(fn(&str, &dyn Display, Option<&Location>)),
+}
+
+/// Current policy, accessed only in critical section.
+struct PolicyCell(UnsafeCell<PanicPolicy>);
+
+unsafe impl Sync for PolicyCell {}
+
+static POLICY: PolicyCell = PolicyCell(UnsafeCell::new(PanicPolicy::Abort));
+
+/// Set what to do when task panics.
+///
+/// Must not be called from interrupt.
+pub fn set_policy(policy: PanicPolicy) {
+    {
+        let _cs = freertos::CriticalRegion::enter();
+        unsafe { *POLICY.0.get() = policy };
+    }
+    init();
+}
+
+/// Current panic policy.
+///
+/// Must not be called from interrupt.
+pub fn policy() -> PanicPolicy {
+    let _cs = freertos::CriticalRegion::enter();
+    unsafe { *POLICY.0.get() }
+}
+
+/// Make panics handled according to policy.
+///
+/// Installs `std` panic hook when `hosted` feature is enabled, otherwise does nothing.
+pub(crate) fn init() {
+    #[cfg(feature = "hosted")]
+    hosted::install_hook();
+}
+
+/// Panic message with location, displayed the same way as `PanicInfo`.
+struct Report<'a> {
+    message: &'a dyn Display,
+    location: Option<&'a Location<'a>>,
+}
+
+impl Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some(location) => write!(f, "panicked at {}:\n{}", location, self.message),
+            None => write!(f, "panicked:\n{}", self.message),
+        }
+    }
+}
+
+/// Handle panic according to current [policy](set_policy).
+///
+/// Panics outside of tasks (e.g. before scheduler is started) always abort.
+/// Panics inside interrupts aren't detected and must not happen unless policy is [`PanicPolicy::Abort`].
+pub fn handle_panic(message: &dyn Display, location: Option<&Location>) -> ! {
+    let policy = if unsafe { ffi::xTaskGetSchedulerState() } == ffi::TASK_SCHEDULER_RUNNING {
+        policy()
+    } else {
+        PanicPolicy::Abort
+    };
+    let report = Report { message, location };
+    match policy {
+        PanicPolicy::Abort => {
+            println!("PANIC: {}", report);
+            unsafe { __ustd_panic() }
+        }
+        PanicPolicy::Suspend => {
+            println!("PANIC in task '{}': {}", task_name(), report);
+            suspend_panicked()
+        }
+        PanicPolicy::Hook(hook) => {
+            hook(task_name(), message, location);
+            suspend_panicked()
+        }
+    }
+}
+
+fn task_name() -> &'static str {
+    unsafe { CStr::from_ptr(ffi::pcTaskGetName(null())) }
+        .to_str()
+        .unwrap_or("?")
+}
+
+#[cfg(feature = "panic")]
 #[panic_handler]
-fn panic(info: &PanicInfo) -> ! {
-    let _ = println!("PANIC: {}", info);
-    unsafe { __ustd_panic() }
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    handle_panic(&info.message(), info.location())
+}
+
+#[cfg(feature = "hosted")]
+mod hosted {
+    extern crate std;
+
+    use std::{boxed::Box, panic::set_hook, sync::Once};
+
+    pub fn install_hook() {
+        static ONCE: Once = Once::new();
+        ONCE.call_once(|| {
+            set_hook(Box::new(|info| {
+                let message = info.payload_as_str().unwrap_or("Box<dyn Any>");
+                super::handle_panic(&message, info.location())
+            }))
+        });
+    }
 }
@@ -0,0 +1,14 @@
+//! Backend part of program termination, see `ustd::process`.
+//!
+//! Glue code must provide `__ustd_exit_code(int code)` that terminates the program with `code`.
+
+use core::ffi::c_int;
+
+extern "C" {
+    fn __ustd_exit_code(code: c_int) -> !;
+}
+
+/// Terminate the whole program with exit `code`.
+pub fn exit(code: i32) -> ! {
+    unsafe { __ustd_exit_code(code as c_int) }
+}
@@ -1,30 +1,91 @@
-use super::task::{InterruptContext, TaskContext};
+extern crate alloc;
+
+use super::task::{with_wakeup, InterruptContext, TaskContext};
 use crate::{
     error::Error,
+    ffi::{self, EventGroupHandle},
     task::{BlockingContext, Context},
     time::{duration_into_freertos, TimerContext},
 };
+use alloc::collections::VecDeque;
 use core::{
+    ffi::c_void,
+    marker::PhantomData,
+    mem::{needs_drop, size_of, ManuallyDrop, MaybeUninit},
     ops::{Deref, DerefMut},
+    ptr::{self, null, null_mut},
     time::Duration,
 };
-use freertos::{Duration as FreeRtosDuration, FreeRtosError};
+use freertos::{
+    Duration as FreeRtosDuration, DurationTicks, FreeRtosError, FreeRtosQueueHandle,
+    FreeRtosUBaseType,
+};
 
 mod sealed {
-    use freertos::{Duration as FreeRtosDuration, Semaphore};
+    use crate::ffi::{EventBits, EventGroupHandle};
+    use core::ffi::c_void;
+    use freertos::{Duration as FreeRtosDuration, FreeRtosQueueHandle, Semaphore};
 
     pub trait SyncContext {
         fn semaphore_try_give(&mut self, sem: &Semaphore) -> bool;
         fn semaphore_try_take(&mut self, sem: &Semaphore) -> bool;
+
+        /// # Safety
+        /// `item` must point to a valid item of the queue type.
+        unsafe fn queue_try_send(
+            &mut self,
+            queue: FreeRtosQueueHandle,
+            item: *const c_void,
+        ) -> bool;
+        /// # Safety
+        /// `item` must point to a memory suitable to store an item of the queue type.
+        unsafe fn queue_try_recv(&mut self, queue: FreeRtosQueueHandle, item: *mut c_void) -> bool;
+        fn queue_len(&mut self, queue: FreeRtosQueueHandle) -> usize;
+
+        fn event_group_set_bits(&mut self, group: EventGroupHandle, bits: EventBits) -> bool;
+        fn event_group_clear_bits(&mut self, group: EventGroupHandle, bits: EventBits) -> bool;
+        fn event_group_get_bits(&mut self, group: EventGroupHandle) -> EventBits;
     }
 
     pub trait SyncBlockingContext: SyncContext {
         fn semaphore_take(&mut self, sem: &Semaphore, timeout: FreeRtosDuration) -> bool;
+
+        /// # Safety
+        /// Same as for [`SyncContext::queue_try_send`].
+        unsafe fn queue_send(
+            &mut self,
+            queue: FreeRtosQueueHandle,
+            item: *const c_void,
+            timeout: FreeRtosDuration,
+        ) -> bool;
+        /// # Safety
+        /// Same as for [`SyncContext::queue_try_recv`].
+        unsafe fn queue_recv(
+            &mut self,
+            queue: FreeRtosQueueHandle,
+            item: *mut c_void,
+            timeout: FreeRtosDuration,
+        ) -> bool;
     }
 }
 
 pub(crate) use sealed::{SyncBlockingContext, SyncContext};
 
+unsafe fn queue_send(
+    queue: FreeRtosQueueHandle,
+    item: *const c_void,
+    timeout: FreeRtosDuration,
+) -> bool {
+    ffi::xQueueGenericSend(queue, item, timeout.to_ticks(), ffi::QUEUE_SEND_TO_BACK) == ffi::PD_TRUE
+}
+unsafe fn queue_recv(
+    queue: FreeRtosQueueHandle,
+    item: *mut c_void,
+    timeout: FreeRtosDuration,
+) -> bool {
+    ffi::xQueueReceive(queue, item, timeout.to_ticks()) == ffi::PD_TRUE
+}
+
 impl SyncContext for TaskContext {
     fn semaphore_try_give(&mut self, sem: &freertos::Semaphore) -> bool {
         sem.give()
@@ -36,6 +97,26 @@ impl SyncContext for TaskContext {
             Err(_) => unreachable!(),
         }
     }
+    unsafe fn queue_try_send(&mut self, queue: FreeRtosQueueHandle, item: *const c_void) -> bool {
+        queue_send(queue, item, FreeRtosDuration::zero())
+    }
+    unsafe fn queue_try_recv(&mut self, queue: FreeRtosQueueHandle, item: *mut c_void) -> bool {
+        queue_recv(queue, item, FreeRtosDuration::zero())
+    }
+    fn queue_len(&mut self, queue: FreeRtosQueueHandle) -> usize {
+        unsafe { ffi::uxQueueMessagesWaiting(queue) as usize }
+    }
+    fn event_group_set_bits(&mut self, group: EventGroupHandle, bits: ffi::EventBits) -> bool {
+        unsafe { ffi::xEventGroupSetBits(group, bits) };
+        true
+    }
+    fn event_group_clear_bits(&mut self, group: EventGroupHandle, bits: ffi::EventBits) -> bool {
+        unsafe { ffi::xEventGroupClearBits(group, bits) };
+        true
+    }
+    fn event_group_get_bits(&mut self, group: EventGroupHandle) -> ffi::EventBits {
+        unsafe { ffi::xEventGroupClearBits(group, 0) }
+    }
 }
 impl SyncContext for TimerContext<'_> {
     fn semaphore_try_give(&mut self, sem: &freertos::Semaphore) -> bool {
@@ -48,6 +129,26 @@ impl SyncContext for TimerContext<'_> {
             Err(_) => unreachable!(),
         }
     }
+    unsafe fn queue_try_send(&mut self, queue: FreeRtosQueueHandle, item: *const c_void) -> bool {
+        queue_send(queue, item, FreeRtosDuration::zero())
+    }
+    unsafe fn queue_try_recv(&mut self, queue: FreeRtosQueueHandle, item: *mut c_void) -> bool {
+        queue_recv(queue, item, FreeRtosDuration::zero())
+    }
+    fn queue_len(&mut self, queue: FreeRtosQueueHandle) -> usize {
+        unsafe { ffi::uxQueueMessagesWaiting(queue) as usize }
+    }
+    fn event_group_set_bits(&mut self, group: EventGroupHandle, bits: ffi::EventBits) -> bool {
+        unsafe { ffi::xEventGroupSetBits(group, bits) };
+        true
+    }
+    fn event_group_clear_bits(&mut self, group: EventGroupHandle, bits: ffi::EventBits) -> bool {
+        unsafe { ffi::xEventGroupClearBits(group, bits) };
+        true
+    }
+    fn event_group_get_bits(&mut self, group: EventGroupHandle) -> ffi::EventBits {
+        unsafe { ffi::xEventGroupClearBits(group, 0) }
+    }
 }
 impl SyncBlockingContext for TaskContext {
     fn semaphore_take(&mut self, sem: &freertos::Semaphore, timeout: FreeRtosDuration) -> bool {
@@ -57,6 +158,22 @@ impl SyncBlockingContext for TaskContext {
             Err(_) => unreachable!(),
         }
     }
+    unsafe fn queue_send(
+        &mut self,
+        queue: FreeRtosQueueHandle,
+        item: *const c_void,
+        timeout: FreeRtosDuration,
+    ) -> bool {
+        queue_send(queue, item, timeout)
+    }
+    unsafe fn queue_recv(
+        &mut self,
+        queue: FreeRtosQueueHandle,
+        item: *mut c_void,
+        timeout: FreeRtosDuration,
+    ) -> bool {
+        queue_recv(queue, item, timeout)
+    }
 }
 impl SyncContext for InterruptContext {
     fn semaphore_try_give(&mut self, sem: &freertos::Semaphore) -> bool {
@@ -65,6 +182,32 @@ impl SyncContext for InterruptContext {
     fn semaphore_try_take(&mut self, sem: &freertos::Semaphore) -> bool {
         sem.take_from_isr(&mut self.inner)
     }
+    unsafe fn queue_try_send(&mut self, queue: FreeRtosQueueHandle, item: *const c_void) -> bool {
+        ffi::xQueueGenericSendFromISR(
+            queue,
+            item,
+            self.inner.get_task_field_mut(),
+            ffi::QUEUE_SEND_TO_BACK,
+        ) == ffi::PD_TRUE
+    }
+    unsafe fn queue_try_recv(&mut self, queue: FreeRtosQueueHandle, item: *mut c_void) -> bool {
+        ffi::xQueueReceiveFromISR(queue, item, self.inner.get_task_field_mut()) == ffi::PD_TRUE
+    }
+    fn queue_len(&mut self, queue: FreeRtosQueueHandle) -> usize {
+        unsafe { ffi::uxQueueMessagesWaitingFromISR(queue) as usize }
+    }
+    fn event_group_set_bits(&mut self, group: EventGroupHandle, bits: ffi::EventBits) -> bool {
+        unsafe {
+            ffi::xEventGroupSetBitsFromISR(group, bits, self.inner.get_task_field_mut())
+                == ffi::PD_TRUE
+        }
+    }
+    fn event_group_clear_bits(&mut self, group: EventGroupHandle, bits: ffi::EventBits) -> bool {
+        unsafe { ffi::xEventGroupClearBitsFromISR(group, bits) == ffi::PD_TRUE }
+    }
+    fn event_group_get_bits(&mut self, group: EventGroupHandle) -> ffi::EventBits {
+        unsafe { ffi::xEventGroupGetBitsFromISR(group) }
+    }
 }
 
 pub struct Semaphore(freertos::Semaphore);
@@ -86,6 +229,305 @@ impl Semaphore {
     }
 }
 
+/// Counting semaphore.
+///
+/// Internally a native FreeRTOS queue of zero-sized items.
+pub struct CountingSemaphore {
+    handle: FreeRtosQueueHandle,
+    max_count: usize,
+}
+
+unsafe impl Send for CountingSemaphore {}
+unsafe impl Sync for CountingSemaphore {}
+
+impl CountingSemaphore {
+    /// Create semaphore that can be given up to `max_count` times with `initial_count` already given.
+    pub fn new(max_count: usize, initial_count: usize) -> Result<Self, Error> {
+        if max_count == 0 || initial_count > max_count {
+            return Err(FreeRtosError::InvalidQueueSize);
+        }
+        let handle = unsafe {
+            ffi::xQueueCreateCountingSemaphore(
+                max_count as FreeRtosUBaseType,
+                initial_count as FreeRtosUBaseType,
+            )
+        };
+        if handle.is_null() {
+            return Err(FreeRtosError::OutOfMemory);
+        }
+        Ok(Self { handle, max_count })
+    }
+
+    /// Maximum count of semaphore.
+    pub fn max_count(&self) -> usize {
+        self.max_count
+    }
+
+    /// Current count of semaphore.
+    pub fn count<C: Context>(&self, cx: &mut C) -> usize {
+        cx.queue_len(self.handle)
+    }
+
+    /// Try to increment semaphore count.
+    ///
+    /// Returns `true` on success, `false` when count is already maximal.
+    pub fn try_give<C: Context>(&self, cx: &mut C) -> bool {
+        unsafe { cx.queue_try_send(self.handle, null()) }
+    }
+
+    /// Try to decrement semaphore count.
+    ///
+    /// Returns `true` on success, `false` when count is zero.
+    pub fn try_take<C: Context>(&self, cx: &mut C) -> bool {
+        unsafe { cx.queue_try_recv(self.handle, null_mut()) }
+    }
+
+    /// Increment semaphore count, waiting for it to become less than maximal.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns `true` on success, `false` when timed out.
+    pub fn give<C: BlockingContext>(&self, cx: &mut C, timeout: Option<Duration>) -> bool {
+        unsafe { cx.queue_send(self.handle, null(), duration_into_freertos(timeout)) }
+    }
+
+    /// Decrement semaphore count, waiting for it to become non-zero.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns `true` on success, `false` when timed out.
+    pub fn take<C: BlockingContext>(&self, cx: &mut C, timeout: Option<Duration>) -> bool {
+        unsafe { cx.queue_recv(self.handle, null_mut(), duration_into_freertos(timeout)) }
+    }
+}
+
+impl Drop for CountingSemaphore {
+    fn drop(&mut self) {
+        unsafe { ffi::vQueueDelete(self.handle) };
+    }
+}
+
+/// Bounded multi-producer multi-consumer queue.
+///
+/// Wraps native FreeRTOS queue. Items are moved into queue storage by copying their bytes.
+pub struct Queue<T> {
+    handle: FreeRtosQueueHandle,
+    capacity: usize,
+    _p: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+impl<T> Queue<T> {
+    /// Create empty queue that can hold up to `capacity` items.
+    pub fn new(capacity: usize) -> Result<Self, Error> {
+        if capacity == 0 {
+            return Err(FreeRtosError::InvalidQueueSize);
+        }
+        let handle = unsafe {
+            ffi::xQueueGenericCreate(
+                capacity as FreeRtosUBaseType,
+                size_of::<T>() as FreeRtosUBaseType,
+                ffi::QUEUE_TYPE_BASE,
+            )
+        };
+        if handle.is_null() {
+            return Err(FreeRtosError::OutOfMemory);
+        }
+        Ok(Self {
+            handle,
+            capacity,
+            _p: PhantomData,
+        })
+    }
+
+    /// Maximum number of items in queue.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Try to push `item` to the back of queue.
+    ///
+    /// Returns `item` back when queue is full.
+    pub fn try_send<C: Context>(&self, cx: &mut C, item: T) -> Result<(), T> {
+        let item = ManuallyDrop::new(item);
+        if unsafe { cx.queue_try_send(self.handle, &*item as *const T as *const c_void) } {
+            Ok(())
+        } else {
+            Err(ManuallyDrop::into_inner(item))
+        }
+    }
+
+    /// Try to pop item from the front of queue.
+    ///
+    /// Returns `None` when queue is empty.
+    pub fn try_recv<C: Context>(&self, cx: &mut C) -> Option<T> {
+        let mut item = MaybeUninit::<T>::uninit();
+        if unsafe { cx.queue_try_recv(self.handle, item.as_mut_ptr() as *mut c_void) } {
+            Some(unsafe { item.assume_init() })
+        } else {
+            None
+        }
+    }
+
+    /// Push `item` to the back of queue, waiting for free space.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns `item` back when timed out.
+    pub fn send<C: BlockingContext>(
+        &self,
+        cx: &mut C,
+        item: T,
+        timeout: Option<Duration>,
+    ) -> Result<(), T> {
+        let item = ManuallyDrop::new(item);
+        if unsafe {
+            cx.queue_send(
+                self.handle,
+                &*item as *const T as *const c_void,
+                duration_into_freertos(timeout),
+            )
+        } {
+            Ok(())
+        } else {
+            Err(ManuallyDrop::into_inner(item))
+        }
+    }
+
+    /// Pop item from the front of queue, waiting for it to appear.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns `None` when timed out.
+    pub fn recv<C: BlockingContext>(&self, cx: &mut C, timeout: Option<Duration>) -> Option<T> {
+        let mut item = MaybeUninit::<T>::uninit();
+        if unsafe {
+            cx.queue_recv(
+                self.handle,
+                item.as_mut_ptr() as *mut c_void,
+                duration_into_freertos(timeout),
+            )
+        } {
+            Some(unsafe { item.assume_init() })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        if needs_drop::<T>() {
+            let mut item = MaybeUninit::<T>::uninit();
+            while unsafe {
+                queue_recv(
+                    self.handle,
+                    item.as_mut_ptr() as *mut c_void,
+                    FreeRtosDuration::zero(),
+                )
+            } {
+                unsafe { item.assume_init_drop() };
+            }
+        }
+        unsafe { ffi::vQueueDelete(self.handle) };
+    }
+}
+
+#[doc(hidden)]
+pub use ffi::EventBits;
+
+/// Native FreeRTOS event group, see `ustd::sync::EventGroup`.
+///
+/// Setting and clearing bits from interrupt context is deferred to timer task.
+#[doc(hidden)]
+pub struct RawEventGroup {
+    handle: EventGroupHandle,
+}
+
+unsafe impl Send for RawEventGroup {}
+unsafe impl Sync for RawEventGroup {}
+
+impl RawEventGroup {
+    pub fn new() -> Result<Self, Error> {
+        let handle = unsafe { ffi::xEventGroupCreate() };
+        if handle.is_null() {
+            return Err(FreeRtosError::OutOfMemory);
+        }
+        Ok(Self { handle })
+    }
+
+    pub fn set_bits<C: Context>(&self, cx: &mut C, bits: EventBits) -> bool {
+        cx.event_group_set_bits(self.handle, bits)
+    }
+
+    pub fn clear_bits<C: Context>(&self, cx: &mut C, bits: EventBits) -> bool {
+        cx.event_group_clear_bits(self.handle, bits)
+    }
+
+    pub fn get_bits<C: Context>(&self, cx: &mut C) -> EventBits {
+        cx.event_group_get_bits(self.handle)
+    }
+
+    pub fn wait_bits<C: BlockingContext>(
+        &self,
+        _cx: &mut C,
+        bits: EventBits,
+        wait_for_all: bool,
+        clear_on_exit: bool,
+        timeout: Option<Duration>,
+    ) -> Option<EventBits> {
+        let value = unsafe {
+            ffi::xEventGroupWaitBits(
+                self.handle,
+                bits,
+                if clear_on_exit {
+                    ffi::PD_TRUE
+                } else {
+                    ffi::PD_FALSE
+                },
+                if wait_for_all {
+                    ffi::PD_TRUE
+                } else {
+                    ffi::PD_FALSE
+                },
+                duration_into_freertos(timeout).to_ticks(),
+            )
+        };
+        let met = if wait_for_all {
+            value & bits == bits
+        } else {
+            value & bits != 0
+        };
+        met.then_some(value)
+    }
+
+    pub fn sync<C: BlockingContext>(
+        &self,
+        _cx: &mut C,
+        set: EventBits,
+        wait: EventBits,
+        timeout: Option<Duration>,
+    ) -> Option<EventBits> {
+        let value = unsafe {
+            ffi::xEventGroupSync(
+                self.handle,
+                set,
+                wait,
+                duration_into_freertos(timeout).to_ticks(),
+            )
+        };
+        (value & wait == wait).then_some(value)
+    }
+}
+
+impl Drop for RawEventGroup {
+    fn drop(&mut self) {
+        unsafe { ffi::vEventGroupDelete(self.handle) };
+    }
+}
+
 pub struct Mutex<T>(freertos::Mutex<T>);
 
 impl<T> Mutex<T> {
@@ -98,7 +540,7 @@ impl<T> Mutex<T> {
 
     pub fn try_lock(&self, _cx: &mut TaskContext) -> Result<Option<MutexGuard<'_, T>>, Error> {
         match self.0.lock(freertos::Duration::zero()) {
-            Ok(guard) => Ok(Some(MutexGuard(guard))),
+            Ok(guard) => Ok(Some(MutexGuard { mutex: self, guard })),
             Err(FreeRtosError::Timeout | FreeRtosError::MutexTimeout) => Ok(None),
             Err(other) => Err(other),
         }
@@ -108,21 +550,123 @@ impl<T> Mutex<T> {
         _cx: &mut TaskContext,
         timeout: Option<Duration>,
     ) -> Result<MutexGuard<'_, T>, Error> {
-        self.0.lock(duration_into_freertos(timeout)).map(MutexGuard)
+        self.0
+            .lock(duration_into_freertos(timeout))
+            .map(|guard| MutexGuard { mutex: self, guard })
     }
 }
 
-pub struct MutexGuard<'a, T>(freertos::MutexGuard<'a, T, freertos::MutexNormal>);
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+    guard: freertos::MutexGuard<'a, T, freertos::MutexNormal>,
+}
 
 impl<T> Deref for MutexGuard<'_, T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
-        self.0.deref()
+        self.guard.deref()
     }
 }
 
 impl<T> DerefMut for MutexGuard<'_, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.0.deref_mut()
+        self.guard.deref_mut()
+    }
+}
+
+/// Wake-up semaphore of waiting task, valid while it is registered in [`Condvar`].
+struct Waiter(*const freertos::Semaphore);
+
+unsafe impl Send for Waiter {}
+
+/// Condition variable to be used with [`Mutex`].
+///
+/// Each waiting task is blocked on its own binary semaphore which is given on notification.
+/// Tasks spawned by ustd reuse the same semaphore for all waits.
+pub struct Condvar {
+    waiters: freertos::Mutex<VecDeque<Waiter>>,
+}
+
+impl Condvar {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            waiters: freertos::Mutex::new(VecDeque::new())?,
+        })
+    }
+
+    /// Release `guard` and wait for notification, then lock the mutex again.
+    pub fn wait<'a, T>(
+        &self,
+        cx: &mut TaskContext,
+        guard: MutexGuard<'a, T>,
+    ) -> Result<MutexGuard<'a, T>, Error> {
+        self.wait_timeout(cx, guard, None).map(|(guard, _)| guard)
+    }
+
+    /// Release `guard` and wait for notification, then lock the mutex again.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns `true` in second element on notification, `false` when timed out.
+    pub fn wait_timeout<'a, T>(
+        &self,
+        cx: &mut TaskContext,
+        guard: MutexGuard<'a, T>,
+        timeout: Option<Duration>,
+    ) -> Result<(MutexGuard<'a, T>, bool), Error> {
+        let mutex = guard.mutex;
+        let notified = with_wakeup(|wakeup| {
+            // Waiter is registered before releasing the mutex so that no notification is missed.
+            self.waiters
+                .lock(freertos::Duration::infinite())?
+                .push_back(Waiter(wakeup));
+            drop(guard);
+
+            if wakeup.take(duration_into_freertos(timeout)).is_ok() {
+                return Ok(true);
+            }
+            let mut waiters = self.waiters.lock(freertos::Duration::infinite())?;
+            match waiters.iter().position(|w| ptr::eq(w.0, wakeup)) {
+                Some(index) => {
+                    waiters.remove(index);
+                    Ok(false)
+                }
+                // Notified right after timeout, the semaphore has been given under the lock.
+                None => {
+                    let _ = wakeup.take(freertos::Duration::zero());
+                    Ok(true)
+                }
+            }
+        })?;
+        Ok((mutex.lock(cx, None)?, notified))
+    }
+
+    /// Wait for notifications while `condition` is true.
+    pub fn wait_while<'a, T, F: FnMut(&mut T) -> bool>(
+        &self,
+        cx: &mut TaskContext,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: F,
+    ) -> Result<MutexGuard<'a, T>, Error> {
+        while condition(&mut guard) {
+            guard = self.wait(cx, guard)?;
+        }
+        Ok(guard)
+    }
+
+    /// Wake up one waiting task if any.
+    pub fn notify_one(&self, _cx: &mut TaskContext) {
+        let mut waiters = self.waiters.lock(freertos::Duration::infinite()).unwrap();
+        if let Some(waiter) = waiters.pop_front() {
+            unsafe { &*waiter.0 }.give();
+        }
+    }
+
+    /// Wake up all waiting tasks.
+    pub fn notify_all(&self, _cx: &mut TaskContext) {
+        let mut waiters = self.waiters.lock(freertos::Duration::infinite()).unwrap();
+        for waiter in waiters.drain(..) {
+            unsafe { &*waiter.0 }.give();
+        }
     }
 }
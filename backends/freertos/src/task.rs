@@ -3,13 +3,49 @@ extern crate alloc;
 use super::sync::{SyncBlockingContext, SyncContext};
 use crate::{
     error::Error,
-    time::{duration_into_freertos, TimeContext},
+    ffi, panic,
+    time::{duration_into_freertos, TimeContext, TimerContext},
 };
-use alloc::sync::Arc;
-use core::{marker::PhantomData, time::Duration};
-use freertos::FreeRtosTaskHandle;
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::{
+    any::Any,
+    cell::UnsafeCell,
+    ffi::c_void,
+    fmt,
+    marker::PhantomData,
+    mem::take,
+    ptr::{null, null_mut, NonNull},
+    time::Duration,
+};
+use freertos::{FreeRtosBaseType, FreeRtosTaskHandle, TaskNotification};
+
+mod sealed {
+    use freertos::TaskNotification;
 
-pub trait Context: SyncContext + TimeContext {}
+    pub trait NotifyContext {
+        fn task_notify(&mut self, task: &freertos::Task, notification: TaskNotification);
+    }
+}
+pub(crate) use sealed::NotifyContext;
+
+impl NotifyContext for TaskContext {
+    fn task_notify(&mut self, task: &freertos::Task, notification: TaskNotification) {
+        task.notify(notification)
+    }
+}
+impl NotifyContext for TimerContext<'_> {
+    fn task_notify(&mut self, task: &freertos::Task, notification: TaskNotification) {
+        task.notify(notification)
+    }
+}
+impl NotifyContext for InterruptContext {
+    fn task_notify(&mut self, task: &freertos::Task, notification: TaskNotification) {
+        // Can fail only for actions that don't overwrite value.
+        let _ = task.notify_from_isr(&mut self.inner, notification);
+    }
+}
+
+pub trait Context: SyncContext + TimeContext + NotifyContext {}
 
 pub trait BlockingContext: Context + SyncBlockingContext {
     fn sleep(&mut self, duration: Option<Duration>);
@@ -23,15 +59,161 @@ pub type Priority = u8;
 #[derive(Clone, Debug)]
 pub struct Task(freertos::Task);
 
+/// How notification value is updated by [`Task::notify`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NotifyAction {
+    /// Leave value unchanged.
+    NoAction,
+    /// Bitwise OR value with given one.
+    SetBits,
+    /// Increment value, given one is ignored.
+    Increment,
+    /// Replace value with given one.
+    Overwrite,
+}
+
 impl Task {
     pub fn id(&self) -> TaskId {
         TaskId(self.0.raw_handle())
     }
+    /// Send notification to the task, updating its notification value according to `action`.
+    ///
+    /// Uses native FreeRTOS task notification.
+    pub fn notify<C: Context>(&self, cx: &mut C, value: u32, action: NotifyAction) {
+        let notification = match action {
+            NotifyAction::NoAction => TaskNotification::NoAction,
+            NotifyAction::SetBits => TaskNotification::SetBits(value),
+            NotifyAction::Increment => TaskNotification::Increment,
+            NotifyAction::Overwrite => TaskNotification::OverwriteValue(value),
+        };
+        cx.task_notify(&self.0, notification)
+    }
 }
 
-pub struct Handle {
+/// Task handle that allows to wait for task to finish and get its result.
+pub struct JoinHandle<T> {
     task: freertos::Task,
     done: Arc<freertos::Semaphore>,
+    result: Arc<ResultSlot<T>>,
+    /// Result has already been taken or found missing.
+    joined: bool,
+}
+
+/// Storage for task result.
+///
+/// Written by task before giving `done` semaphore and read by [`JoinHandle`] after taking it.
+struct ResultSlot<T>(UnsafeCell<Option<T>>);
+
+unsafe impl<T: Send> Send for ResultSlot<T> {}
+unsafe impl<T: Send> Sync for ResultSlot<T> {}
+
+/// Index of thread local storage pointer reserved for ustd.
+///
+/// Requires `configNUM_THREAD_LOCAL_STORAGE_POINTERS` to be at least `1`.
+const TLS_INDEX: FreeRtosBaseType = 0;
+
+/// Values of task locals indexed by key, see `ustd::task::TaskLocal`.
+///
+/// Values are boxed and stored as raw pointers, so that references to them stay valid while locals are modified.
+#[doc(hidden)]
+pub type Locals = Vec<Option<NonNull<dyn Any>>>;
+
+/// Data of task spawned by ustd.
+///
+/// Lives on the stack of the task and is pointed by its thread local storage pointer.
+struct TaskData {
+    done: Arc<freertos::Semaphore>,
+    /// Given to wake up the task blocked on [`Condvar`](crate::sync::Condvar).
+    wakeup: freertos::Semaphore,
+    /// Accessed only by the task itself.
+    locals: UnsafeCell<Locals>,
+}
+
+impl TaskData {
+    /// Get data of current task.
+    ///
+    /// Returns `None` if current task isn't spawned by ustd.
+    fn current<'a>() -> Option<&'a Self> {
+        unsafe {
+            (ffi::pvTaskGetThreadLocalStoragePointer(null(), TLS_INDEX) as *const Self).as_ref()
+        }
+    }
+    fn run<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        unsafe {
+            ffi::vTaskSetThreadLocalStoragePointer(
+                null(),
+                TLS_INDEX,
+                self as *const _ as *mut c_void,
+            )
+        };
+        let result = f();
+        self.clear_locals();
+        unsafe { ffi::vTaskSetThreadLocalStoragePointer(null(), TLS_INDEX, null_mut()) };
+        result
+    }
+
+    /// Drop task locals.
+    ///
+    /// Values accessed by destructors of other values are dropped too.
+    fn clear_locals(&self) {
+        loop {
+            let values = take(unsafe { &mut *self.locals.get() });
+            if values.is_empty() {
+                break;
+            }
+            for value in values.into_iter().flatten() {
+                drop(unsafe { Box::from_raw(value.as_ptr()) });
+            }
+        }
+    }
+}
+
+/// Task locals of current task, `None` if the task isn't spawned by ustd.
+///
+/// The pointer is valid until the task finishes and must be accessed only by the task itself.
+#[doc(hidden)]
+pub fn current_locals() -> Option<*mut Locals> {
+    TaskData::current().map(|data| data.locals.get())
+}
+
+/// Call `f` with semaphore that is given to wake up current task.
+///
+/// Tasks spawned by ustd have their own semaphore, for other tasks it is created for each call.
+pub(crate) fn with_wakeup<R>(
+    f: impl FnOnce(&freertos::Semaphore) -> Result<R, Error>,
+) -> Result<R, Error> {
+    match TaskData::current() {
+        Some(data) => f(&data.wakeup),
+        None => f(&freertos::Semaphore::new_binary()?),
+    }
+}
+
+/// Report panic of current task to its [`JoinHandle`] and suspend the task forever.
+pub(crate) fn suspend_panicked() -> ! {
+    if let Some(data) = TaskData::current() {
+        data.done.give();
+    }
+    loop {
+        unsafe { ffi::vTaskSuspend(null()) };
+    }
+}
+
+/// Error returned by [`JoinHandle::join`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum JoinError {
+    /// Task hasn't finished in time.
+    Timeout,
+    /// Task has finished without result because of panic.
+    Panicked,
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "Task join timed out"),
+            Self::Panicked => write!(f, "Task panicked"),
+        }
+    }
 }
 
 pub struct TaskContext {
@@ -40,17 +222,42 @@ pub struct TaskContext {
     _p: PhantomData<*const ()>,
 }
 
-impl Handle {
+impl<T> JoinHandle<T> {
     pub fn task(&self) -> Task {
         Task(self.task.clone())
     }
-    pub fn join<C: BlockingContext>(&self, _cx: &mut C, timeout: Option<Duration>) -> bool {
+    /// Wait for task to finish.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns `true` when task is finished, `false` when timed out.
+    pub fn wait<C: BlockingContext>(&self, _cx: &mut C, timeout: Option<Duration>) -> bool {
         let done = self.done.take(duration_into_freertos(timeout)).is_ok();
         if done {
             self.done.give();
         }
         done
     }
+    /// Wait for task to finish and take its result.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    /// If timed out then the task continues to run and can be joined again.
+    ///
+    /// Panics if the task has already been joined successfully or found panicked.
+    pub fn join<C: BlockingContext>(
+        &mut self,
+        cx: &mut C,
+        timeout: Option<Duration>,
+    ) -> Result<T, JoinError> {
+        assert!(!self.joined, "Task has already been joined");
+        if self.wait(cx, timeout) {
+            self.joined = true;
+            // Task has finished so nobody else accesses the slot.
+            unsafe { (*self.result.0.get()).take() }.ok_or(JoinError::Panicked)
+        } else {
+            Err(JoinError::Timeout)
+        }
+    }
 }
 
 impl TaskContext {
@@ -64,6 +271,19 @@ impl TaskContext {
     pub fn task(&mut self) -> Task {
         Task(self.task.clone())
     }
+
+    /// Wait for notification sent by [`Task::notify`] to current task.
+    ///
+    /// When notification is received then bits of `clear_mask` are cleared in notification value.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns notification value (before clearing), `None` when timed out.
+    pub fn wait_notification(&mut self, clear_mask: u32, timeout: Option<Duration>) -> Option<u32> {
+        self.task
+            .wait_for_notification(0, clear_mask, duration_into_freertos(timeout))
+            .ok()
+    }
 }
 
 impl Context for TaskContext {}
@@ -116,27 +336,46 @@ impl Builder {
         self.0.priority(freertos::TaskPriority(priority));
         self
     }
-    pub fn spawn<F: FnOnce(&mut TaskContext) + Send + 'static>(
+    pub fn spawn<T: Send + 'static, F: FnOnce(&mut TaskContext) -> T + Send + 'static>(
         self,
         func: F,
-    ) -> Result<Handle, Error> {
-        let done = Arc::new(freertos::Semaphore::new_binary().unwrap());
+    ) -> Result<JoinHandle<T>, Error> {
+        panic::init();
+        let done = Arc::new(freertos::Semaphore::new_binary()?);
+        let wakeup = freertos::Semaphore::new_binary()?;
+        let result = Arc::new(ResultSlot(UnsafeCell::new(None)));
         self.0
             .start({
                 let done = done.clone();
+                let result = result.clone();
                 move |task| {
-                    let mut cx = TaskContext {
-                        task,
-                        _p: PhantomData,
+                    let data = TaskData {
+                        done,
+                        wakeup,
+                        locals: UnsafeCell::new(Vec::new()),
                     };
-                    func(&mut cx);
-                    done.give();
+                    let value = data.run(|| {
+                        let mut cx = TaskContext {
+                            task,
+                            _p: PhantomData,
+                        };
+                        func(&mut cx)
+                    });
+                    unsafe { *result.0.get() = Some(value) };
+                    data.done.give();
                 }
             })
-            .map(|task| Handle { task, done })
+            .map(|task| JoinHandle {
+                task,
+                done,
+                result,
+                joined: false,
+            })
     }
 }
 
-pub fn spawn<F: FnOnce(&mut TaskContext) + Send + 'static>(func: F) -> Result<Handle, Error> {
+pub fn spawn<T: Send + 'static, F: FnOnce(&mut TaskContext) -> T + Send + 'static>(
+    func: F,
+) -> Result<JoinHandle<T>, Error> {
     Builder::new().spawn(func)
 }
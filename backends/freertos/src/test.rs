@@ -1,31 +1,94 @@
+//! Backend part of test runner, see `ustd::test`.
+//!
+//! Glue code must provide `__ustd_test_args()` that returns runner arguments as a null-terminated string or `NULL`
+//! in addition to [`process`](crate::process) requirements.
+
+extern crate alloc;
+
 use crate::{
+    panic::{self, PanicPolicy},
     println,
-    task::{self, TaskContext},
+    process::exit,
+    task::{self, TaskContext, TaskId},
+};
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use core::{
+    ffi::{c_char, CStr},
+    fmt::Display,
+    panic::Location,
+    ptr::null_mut,
+    sync::atomic::{AtomicPtr, Ordering},
 };
-use core::sync::atomic::{AtomicUsize, Ordering};
 
 extern "C" {
-    fn __ustd_exit() -> !;
+    fn __ustd_test_args() -> *const c_char;
 }
-fn exit() -> ! {
-    unsafe { __ustd_exit() }
+
+/// Runner arguments returned by glue code, `None` if there are no arguments.
+#[doc(hidden)]
+pub fn args() -> Option<String> {
+    let ptr = unsafe { __ustd_test_args() };
+    if ptr.is_null() {
+        None
+    } else {
+        Some(
+            unsafe { CStr::from_ptr(ptr) }
+                .to_str()
+                .expect("Test arguments are not valid UTF-8")
+                .into(),
+        )
+    }
 }
 
-pub fn run_tests<I: Iterator<Item = (&'static str, fn(&mut TaskContext))>>(iter: I) {
-    static COUNTER: AtomicUsize = AtomicUsize::new(0);
-    for (name, test) in iter {
-        task::spawn(move |cx| {
-            test(cx);
-            println!("test {} ... ok", name);
-            if COUNTER.fetch_sub(1, Ordering::AcqRel) == 1 {
-                println!();
-                println!("test result: ok.");
-                exit();
-            }
-        })
-        .unwrap();
-        COUNTER.fetch_add(1, Ordering::AcqRel);
+type Panics = freertos::Mutex<Vec<(TaskId, String)>>;
+
+/// Messages of panicked tasks.
+static PANICS: AtomicPtr<Panics> = AtomicPtr::new(null_mut());
+
+/// Print and record panic message of current task.
+fn panic_hook(name: &str, message: &dyn Display, location: Option<&Location>) {
+    match location {
+        Some(location) => println!(
+            "PANIC in task '{}': panicked at {}:\n{}",
+            name, location, message
+        ),
+        None => println!("PANIC in task '{}': panicked:\n{}", name, message),
+    }
+    let (Some(mut cx), Some(panics)) = (TaskContext::current(), unsafe {
+        PANICS.load(Ordering::Acquire).as_ref()
+    }) else {
+        return;
+    };
+    if let Ok(mut panics) = panics.lock(freertos::Duration::infinite()) {
+        panics.push((cx.task().id(), format!("{}", message)));
     }
-    println!("running {} tests", COUNTER.load(Ordering::Acquire));
+}
+
+/// Take recorded panic message of task.
+#[doc(hidden)]
+pub fn take_panic(task: TaskId) -> Option<String> {
+    let panics = unsafe { PANICS.load(Ordering::Acquire).as_ref() }?;
+    let mut panics = panics.lock(freertos::Duration::infinite()).ok()?;
+    let index = panics.iter().position(|(id, _)| *id == task)?;
+    Some(panics.swap_remove(index).1)
+}
+
+/// Run test runner `func` in a new task and exit with returned code.
+///
+/// While `func` is running, panicked tasks are suspended, so the failure is reported instead of aborting the program.
+/// Panic messages are recorded and can be taken with [`take_panic`].
+/// Previous panic policy is restored before exit.
+#[doc(hidden)]
+pub fn run<F: FnOnce(&mut TaskContext) -> i32 + Send + 'static>(func: F) {
+    task::spawn(move |cx| {
+        let panics = Box::new(Panics::new(Vec::new()).unwrap());
+        PANICS.store(Box::into_raw(panics), Ordering::Release);
+        let policy = panic::policy();
+        panic::set_policy(PanicPolicy::Hook(panic_hook));
+        let code = func(cx);
+        panic::set_policy(policy);
+        exit(code);
+    })
+    .unwrap();
     freertos::FreeRtosUtils::start_scheduler();
 }
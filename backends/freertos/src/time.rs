@@ -1,67 +1,161 @@
+//! Time measurement and timers.
+//!
+//! Glue code must provide `__ustd_tick_rate_hz()` that returns `configTICK_RATE_HZ`
+//! and `__ustd_tick_overflow_count()` returning the number of tick counter overflows,
+//! the latter must be callable from interrupts.
+
 use crate::{
-    task::{Context, InterruptContext, TaskContext},
+    ffi,
+    task::{BlockingContext, Context, InterruptContext, TaskContext},
     Error,
 };
-use core::{marker::PhantomData, ops::ControlFlow, time::Duration};
-use freertos::{self, DurationTicks, FreeRtosTickType, FreeRtosUtils};
+use core::{marker::PhantomData, mem::MaybeUninit, ops::ControlFlow, time::Duration};
+use freertos::{self, DurationTicks, FreeRtosBaseType, FreeRtosTickType};
+
+extern "C" {
+    fn __ustd_tick_rate_hz() -> u32;
+    fn __ustd_tick_overflow_count() -> FreeRtosBaseType;
+}
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// Frequency of kernel tick.
+pub fn tick_rate_hz() -> u32 {
+    unsafe { __ustd_tick_rate_hz() }
+}
 
+/// Number of ticks in `duration` rounded up to a whole tick.
+///
+/// Saturates to `u64::MAX`.
+pub fn duration_to_ticks(duration: Duration) -> u64 {
+    let ticks = (duration.as_nanos() * tick_rate_hz() as u128).div_ceil(NANOS_PER_SEC as u128);
+    u64::try_from(ticks).unwrap_or(u64::MAX)
+}
+
+/// Duration of `ticks` rounded down to a nanosecond.
+pub fn ticks_to_duration(ticks: u64) -> Duration {
+    let rate = tick_rate_hz() as u64;
+    let nanos = (ticks % rate) * NANOS_PER_SEC / rate;
+    Duration::new(ticks / rate, nanos as u32)
+}
+
+/// Convert timeout to kernel ticks, `None` means infinite timeout.
+///
+/// Finite timeouts saturate to the longest finite wait.
 pub(crate) fn duration_into_freertos(native: Option<Duration>) -> freertos::Duration {
+    let infinite = freertos::Duration::infinite();
     match native {
         Some(t) => {
-            assert!(t.as_millis() < u32::MAX as u128);
-            freertos::Duration::ms(t.as_millis() as u32)
+            let max = infinite.to_ticks() - 1;
+            freertos::Duration::ticks(duration_to_ticks(t).min(max as u64) as FreeRtosTickType)
         }
-        None => freertos::Duration::infinite(),
+        None => infinite,
     }
 }
-pub(crate) fn duration_from_freertos(freertos: freertos::Duration) -> Option<Duration> {
-    if freertos.to_ticks() < freertos::Duration::infinite().to_ticks() {
-        Some(Duration::from_millis(freertos.to_ms() as u64))
-    } else {
-        None
+
+fn extend_tick_count(overflow_count: FreeRtosBaseType, ticks: FreeRtosTickType) -> u64 {
+    // Overflows don't happen with 64-bit tick type.
+    let high = (overflow_count as u64)
+        .checked_shl(FreeRtosTickType::BITS)
+        .unwrap_or(0);
+    high | ticks as u64
+}
+
+/// Tick count extended to 64 bits with the number of tick counter overflows.
+///
+/// Must not be called from interrupt.
+fn tick_count() -> u64 {
+    let mut state = MaybeUninit::<ffi::TimeOut>::uninit();
+    // Tick count and overflow count are read together in critical section.
+    let state = unsafe {
+        ffi::vTaskSetTimeOutState(state.as_mut_ptr());
+        state.assume_init()
+    };
+    extend_tick_count(state.overflow_count, state.time_on_entering)
+}
+
+/// Same as [`tick_count`] but for interrupts.
+fn tick_count_from_isr() -> u64 {
+    loop {
+        let overflows = unsafe { __ustd_tick_overflow_count() };
+        let ticks = unsafe { ffi::xTaskGetTickCountFromISR() };
+        // Retry if tick counter has overflowed in between.
+        if unsafe { __ustd_tick_overflow_count() } == overflows {
+            break extend_tick_count(overflows, ticks);
+        }
     }
 }
 
 mod sealed {
-    use freertos::FreeRtosTickType;
-
     pub trait TimeContext {
-        fn get_tick_count(&mut self) -> FreeRtosTickType;
+        /// Monotonic 64-bit tick count.
+        fn get_tick_count(&mut self) -> u64;
     }
 }
 pub(crate) use sealed::TimeContext;
 
 impl TimeContext for TaskContext {
-    fn get_tick_count(&mut self) -> FreeRtosTickType {
-        FreeRtosUtils::get_tick_count()
+    fn get_tick_count(&mut self) -> u64 {
+        tick_count()
     }
 }
 impl TimeContext for TimerContext<'_> {
-    fn get_tick_count(&mut self) -> FreeRtosTickType {
-        FreeRtosUtils::get_tick_count()
+    fn get_tick_count(&mut self) -> u64 {
+        tick_count()
     }
 }
 impl TimeContext for InterruptContext {
-    fn get_tick_count(&mut self) -> FreeRtosTickType {
-        // TODO: Impl get_tick_count from ISR in FreeRTOS-Rust
-        unimplemented!()
+    fn get_tick_count(&mut self) -> u64 {
+        tick_count_from_isr()
     }
 }
 
-pub struct Instant {
-    start: FreeRtosTickType,
+/// Point in time of kernel tick counter, see `ustd::time::Instant`.
+///
+/// Tick counter overflows are tracked, so instants never wrap around.
+#[doc(hidden)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct RawInstant {
+    ticks: u64,
 }
 
-impl Instant {
+impl RawInstant {
     pub fn now(cx: &mut impl Context) -> Self {
         Self {
-            start: cx.get_tick_count(),
+            ticks: cx.get_tick_count(),
         }
     }
-    pub fn elapsed(&self, cx: &mut impl Context) -> Duration {
-        let now = cx.get_tick_count();
-        let ticks = now.wrapping_sub(self.start);
-        duration_from_freertos(freertos::Duration::ticks(ticks)).unwrap()
+
+    pub fn checked_duration_since(&self, earlier: Self) -> Option<Duration> {
+        self.ticks.checked_sub(earlier.ticks).map(ticks_to_duration)
+    }
+    pub fn checked_add(&self, duration: Duration) -> Option<Self> {
+        Some(Self {
+            ticks: self.ticks.checked_add(duration_to_ticks(duration))?,
+        })
+    }
+    pub fn checked_sub(&self, duration: Duration) -> Option<Self> {
+        Some(Self {
+            ticks: self.ticks.checked_sub(duration_to_ticks(duration))?,
+        })
+    }
+}
+
+/// Block current task until `deadline`, see `ustd::time::sleep_until`.
+///
+/// Wake time is computed by kernel from `deadline` rather than from the moment of call, so it doesn't drift.
+#[doc(hidden)]
+pub fn sleep_until(_cx: &mut impl BlockingContext, deadline: RawInstant) {
+    // Longer delays are split, so that wake time always fits into tick type.
+    const MAX_DELAY: u64 = (FreeRtosTickType::MAX / 2) as u64;
+    loop {
+        let now = tick_count();
+        if now >= deadline.ticks {
+            break;
+        }
+        let mut previous = now as FreeRtosTickType;
+        let delay = (deadline.ticks - now).min(MAX_DELAY) as FreeRtosTickType;
+        unsafe { ffi::vTaskDelayUntil(&mut previous, delay) };
     }
 }
 
@@ -3,3 +3,9 @@ extern crate std;
 use std::io;
 
 pub type Error = io::Error;
+
+/// Error of operation that hasn't completed in time.
+#[doc(hidden)]
+pub fn timeout() -> Error {
+    io::ErrorKind::TimedOut.into()
+}
@@ -1,9 +1,12 @@
-mod macros;
+#[cfg(feature = "single-core")]
+mod sched;
 
 pub mod error;
 pub mod io;
+pub mod process;
 pub mod sync;
 pub mod task;
+pub mod test;
 pub mod time;
 
 pub use error::Error;
@@ -0,0 +1,5 @@
+//! Backend part of program termination, see `ustd::process`.
+
+extern crate std;
+
+pub use std::process::exit;
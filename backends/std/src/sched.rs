@@ -0,0 +1,260 @@
+//! Emulation of single-core scheduler.
+//!
+//! Only one task runs at a time: it holds virtual CPU until it blocks, finishes
+//! or yields CPU to a higher-priority ready task at a preemption point.
+//! Preemption points are calls that may wake up other tasks.
+//! Tasks of equal priority are not time-sliced.
+//!
+//! Threads not registered as tasks (e.g. simulated interrupts) run freely.
+
+extern crate std;
+
+use crate::task::Priority;
+use core::{cell::Cell, time::Duration};
+use std::{
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+    thread_local,
+    time::Instant,
+    vec::Vec,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+    Ready,
+    Running,
+    /// Waiting for notification with `key`.
+    Waiting {
+        key: usize,
+    },
+}
+
+struct Entry {
+    id: usize,
+    priority: Priority,
+    state: State,
+    /// Order of entering current state, to keep FIFO order among tasks of equal priority.
+    seq: u64,
+    notified: bool,
+}
+
+struct Scheduler {
+    entries: Vec<Entry>,
+    running: Option<usize>,
+    next_id: usize,
+    next_seq: u64,
+}
+
+impl Scheduler {
+    const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            running: None,
+            next_id: 0,
+            next_seq: 0,
+        }
+    }
+
+    fn entry(&mut self, id: usize) -> &mut Entry {
+        self.entries.iter_mut().find(|e| e.id == id).unwrap()
+    }
+
+    fn set_state(&mut self, id: usize, state: State) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let entry = self.entry(id);
+        entry.state = state;
+        entry.seq = seq;
+    }
+
+    /// Give free CPU to the highest-priority ready task.
+    fn dispatch(&mut self) {
+        if self.running.is_some() {
+            return;
+        }
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .filter(|e| e.state == State::Ready)
+            .max_by(|a, b| a.priority.cmp(&b.priority).then(b.seq.cmp(&a.seq)))
+        {
+            entry.state = State::Running;
+            self.running = Some(entry.id);
+            SWITCH.notify_all();
+        }
+    }
+
+    /// Release CPU held by task `id`.
+    fn release(&mut self, id: usize) {
+        if self.running == Some(id) {
+            self.running = None;
+        }
+        self.dispatch();
+    }
+}
+
+static SCHEDULER: Mutex<Scheduler> = Mutex::new(Scheduler::new());
+/// Notified on each change of running task.
+static SWITCH: Condvar = Condvar::new();
+
+fn lock() -> MutexGuard<'static, Scheduler> {
+    SCHEDULER.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn wait_running(mut sched: MutexGuard<'static, Scheduler>, id: usize) {
+    while sched.running != Some(id) {
+        sched = SWITCH.wait(sched).unwrap_or_else(PoisonError::into_inner);
+    }
+}
+
+/// Task bound to current thread.
+struct Current(Cell<Option<usize>>);
+
+impl Drop for Current {
+    fn drop(&mut self) {
+        if let Some(id) = self.0.take() {
+            remove(id);
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: Current = const { Current(Cell::new(None)) };
+}
+
+/// Task bound to current thread if any.
+pub fn current() -> Option<usize> {
+    CURRENT.try_with(|c| c.0.get()).ok().flatten()
+}
+
+/// Register a new ready task.
+pub fn create(priority: Priority) -> usize {
+    let mut sched = lock();
+    let id = sched.next_id;
+    sched.next_id += 1;
+    sched.entries.push(Entry {
+        id,
+        priority,
+        state: State::Ready,
+        seq: 0,
+        notified: false,
+    });
+    sched.set_state(id, State::Ready);
+    sched.dispatch();
+    id
+}
+
+/// Bind task `id` to current thread and wait until it is running.
+pub fn start(id: usize) {
+    CURRENT.with(|c| assert!(c.0.replace(Some(id)).is_none()));
+    wait_running(lock(), id);
+}
+
+/// Unregister task.
+pub fn remove(id: usize) {
+    let mut sched = lock();
+    sched.entries.retain(|e| e.id != id);
+    sched.release(id);
+}
+
+/// Register current thread as a task unless it is already registered.
+///
+/// Task is removed when the thread exits.
+pub fn enter(priority: Priority) {
+    if current().is_none() {
+        start(create(priority));
+    }
+}
+
+/// Unregister task bound to current thread.
+pub fn exit() {
+    if let Some(id) = CURRENT.with(|c| c.0.take()) {
+        remove(id);
+    }
+}
+
+/// Block current task until it is notified with `key` or `deadline` is reached.
+///
+/// `guard` is released after the task is registered as waiting so no notification is missed.
+///
+/// Returns `true` when notified.
+pub fn wait<G>(key: usize, deadline: Option<Instant>, guard: G) -> bool {
+    let id = current().unwrap();
+    let mut sched = lock();
+    sched.set_state(id, State::Waiting { key });
+    sched.entry(id).notified = false;
+    drop(guard);
+    sched.release(id);
+    while sched.running != Some(id) {
+        sched = match deadline {
+            Some(deadline) if matches!(sched.entry(id).state, State::Waiting { .. }) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    sched.set_state(id, State::Ready);
+                    sched.dispatch();
+                    continue;
+                }
+                SWITCH
+                    .wait_timeout(sched, deadline - now)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0
+            }
+            _ => SWITCH.wait(sched).unwrap_or_else(PoisonError::into_inner),
+        };
+    }
+    sched.entry(id).notified
+}
+
+/// Make tasks waiting for `key` ready.
+///
+/// When `all` is `false` only the highest-priority task is woken up.
+pub fn notify(key: usize, all: bool) {
+    let mut sched = lock();
+    let mut woken = Vec::new();
+    for entry in sched
+        .entries
+        .iter()
+        .filter(|e| e.state == State::Waiting { key })
+    {
+        woken.push((entry.id, entry.priority, entry.seq));
+    }
+    if !all {
+        woken.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)));
+        woken.truncate(1);
+    }
+    for (id, _, _) in woken {
+        sched.set_state(id, State::Ready);
+        sched.entry(id).notified = true;
+    }
+    sched.dispatch();
+}
+
+/// Yield CPU if there is a ready task of higher priority than current one.
+pub fn preempt() {
+    let Some(id) = current() else {
+        return;
+    };
+    let mut sched = lock();
+    let priority = sched.entry(id).priority;
+    if sched.running == Some(id)
+        && sched
+            .entries
+            .iter()
+            .any(|e| e.state == State::Ready && e.priority > priority)
+    {
+        sched.set_state(id, State::Ready);
+        sched.release(id);
+        wait_running(sched, id);
+    }
+}
+
+/// Block current task for `duration`, infinitely if `None`.
+pub fn sleep(duration: Option<Duration>) {
+    // Deadline that is too far to be represented is never reached.
+    sleep_until(duration.and_then(|d| Instant::now().checked_add(d)));
+}
+
+/// Block current task until `deadline`, infinitely if `None`.
+pub fn sleep_until(deadline: Option<Instant>) {
+    // Zero key is never notified.
+    wait(0, deadline, ());
+}
@@ -1,28 +1,140 @@
 extern crate std;
 
+#[cfg(feature = "single-core")]
+use crate::sched;
 use crate::{
     error::Error,
     task::{BlockingContext, Context, TaskContext},
 };
 use core::{mem::replace, time::Duration};
+#[cfg(feature = "single-core")]
+use std::time::Instant;
 use std::{
+    collections::VecDeque,
     io::ErrorKind,
     ops::{Deref, DerefMut},
-    sync::{Condvar, Mutex as StdMutex, MutexGuard as StdMutexGuard, TryLockError},
-    time::Instant,
+    sync::{Condvar as StdCondvar, Mutex as StdMutex, MutexGuard as StdMutexGuard},
+    vec::Vec,
 };
 
+#[cfg(feature = "single-core")]
+fn key(condvar: &StdCondvar) -> usize {
+    condvar as *const StdCondvar as usize
+}
+
+/// Implementation of [`wait_while`] for task in single-core mode.
+#[cfg(feature = "single-core")]
+fn sched_wait_while<'a, T, F: FnMut(&mut T) -> bool>(
+    condvar: &StdCondvar,
+    mutex: &'a StdMutex<T>,
+    mut guard: StdMutexGuard<'a, T>,
+    timeout: Option<Duration>,
+    mut condition: F,
+) -> (StdMutexGuard<'a, T>, bool) {
+    let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+    loop {
+        if !condition(&mut guard) {
+            break (guard, true);
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            break (guard, false);
+        }
+        sched::wait(key(condvar), deadline, guard);
+        guard = mutex.lock().unwrap();
+    }
+}
+
+/// Wait on `condvar` while `condition` is true.
+///
+/// `guard` must be obtained from `mutex`.
+///
+/// When `timeout` is `None` then wait infinitely.
+///
+/// Returns `false` in second element when timed out.
+#[cfg_attr(not(feature = "single-core"), allow(unused_variables))]
+pub(crate) fn wait_while<'a, T, F: FnMut(&mut T) -> bool>(
+    condvar: &StdCondvar,
+    mutex: &'a StdMutex<T>,
+    guard: StdMutexGuard<'a, T>,
+    timeout: Option<Duration>,
+    condition: F,
+) -> (StdMutexGuard<'a, T>, bool) {
+    #[cfg(feature = "single-core")]
+    if sched::current().is_some() {
+        return sched_wait_while(condvar, mutex, guard, timeout, condition);
+    }
+    match timeout {
+        Some(t) => {
+            let (guard, result) = condvar.wait_timeout_while(guard, t, condition).unwrap();
+            (guard, !result.timed_out())
+        }
+        None => (condvar.wait_while(guard, condition).unwrap(), true),
+    }
+}
+
+/// Wait on `condvar` for notification.
+///
+/// `guard` must be obtained from `mutex`.
+///
+/// When `timeout` is `None` then wait infinitely.
+///
+/// Returns `false` in second element when timed out.
+#[cfg_attr(not(feature = "single-core"), allow(unused_variables))]
+pub(crate) fn wait<'a, T>(
+    condvar: &StdCondvar,
+    mutex: &'a StdMutex<T>,
+    guard: StdMutexGuard<'a, T>,
+    timeout: Option<Duration>,
+) -> Result<(StdMutexGuard<'a, T>, bool), Error> {
+    let poisoned = || Error::other("Poisoned mutex");
+    #[cfg(feature = "single-core")]
+    if sched::current().is_some() {
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+        let notified = sched::wait(key(condvar), deadline, guard);
+        return Ok((mutex.lock().map_err(|_| poisoned())?, notified));
+    }
+    match timeout {
+        Some(t) => {
+            let (guard, result) = condvar.wait_timeout(guard, t).map_err(|_| poisoned())?;
+            Ok((guard, !result.timed_out()))
+        }
+        None => Ok((condvar.wait(guard).map_err(|_| poisoned())?, true)),
+    }
+}
+
+/// Wake up one task waiting on `condvar`.
+pub(crate) fn notify_one(condvar: &StdCondvar) {
+    #[cfg(feature = "single-core")]
+    sched::notify(key(condvar), false);
+    condvar.notify_one();
+}
+
+/// Wake up all tasks waiting on `condvar`.
+pub(crate) fn notify_all(condvar: &StdCondvar) {
+    #[cfg(feature = "single-core")]
+    sched::notify(key(condvar), true);
+    condvar.notify_all();
+}
+
+/// Let woken up task of higher priority run.
+///
+/// Must be called without holding any locks.
+pub(crate) fn preempt() {
+    #[cfg(feature = "single-core")]
+    sched::preempt();
+}
+
 /// Binary semaphore.
 pub struct Semaphore {
     value: StdMutex<bool>,
-    condvar: Condvar,
+    condvar: StdCondvar,
 }
 
 impl Semaphore {
     fn with_value(value: bool) -> Self {
         Self {
             value: StdMutex::new(value),
-            condvar: Condvar::new(),
+            condvar: StdCondvar::new(),
         }
     }
 
@@ -34,7 +146,7 @@ impl Semaphore {
     fn try_give_inner(&self) -> bool {
         let mut guard = self.value.lock().unwrap();
         let prev = replace(&mut *guard, true);
-        self.condvar.notify_one();
+        notify_one(&self.condvar);
         !prev
     }
 
@@ -42,7 +154,9 @@ impl Semaphore {
     ///
     /// Returns `true` on success, `false` when already released.
     pub fn try_give<C: Context>(&self, _cx: &mut C) -> bool {
-        self.try_give_inner()
+        let given = self.try_give_inner();
+        preempt();
+        given
     }
 
     /// Try to acquire semaphore.
@@ -58,24 +172,360 @@ impl Semaphore {
     ///
     /// Returns `true` on success, `false` when timed out.
     pub fn take<C: BlockingContext>(&self, _cx: &mut C, timeout: Option<Duration>) -> bool {
-        let mut guard_slot = Some(self.value.lock().unwrap());
-        let instant = Instant::now();
-        loop {
-            let mut guard = guard_slot.take().unwrap();
-            if replace(&mut *guard, false) {
-                break true;
-            }
-            guard_slot.replace(match timeout {
-                Some(total) => {
-                    let current = instant.elapsed();
-                    if current >= total {
-                        break false;
-                    }
-                    self.condvar.wait_timeout(guard, total - current).unwrap().0
+        let guard = self.value.lock().unwrap();
+        let (mut guard, done) =
+            wait_while(&self.condvar, &self.value, guard, timeout, |value| !*value);
+        if done {
+            *guard = false;
+        }
+        done
+    }
+}
+
+/// Counting semaphore.
+pub struct CountingSemaphore {
+    value: StdMutex<usize>,
+    max_count: usize,
+    condvar: StdCondvar,
+}
+
+impl CountingSemaphore {
+    /// Create semaphore that can be given up to `max_count` times with `initial_count` already given.
+    pub fn new(max_count: usize, initial_count: usize) -> Result<Self, Error> {
+        if max_count == 0 || initial_count > max_count {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+        Ok(Self {
+            value: StdMutex::new(initial_count),
+            max_count,
+            condvar: StdCondvar::new(),
+        })
+    }
+
+    /// Maximum count of semaphore.
+    pub fn max_count(&self) -> usize {
+        self.max_count
+    }
+
+    /// Current count of semaphore.
+    pub fn count<C: Context>(&self, _cx: &mut C) -> usize {
+        *self.value.lock().unwrap()
+    }
+
+    /// Try to increment semaphore count.
+    ///
+    /// Returns `true` on success, `false` when count is already maximal.
+    pub fn try_give<C: Context>(&self, _cx: &mut C) -> bool {
+        let mut guard = self.value.lock().unwrap();
+        if *guard < self.max_count {
+            *guard += 1;
+            notify_all(&self.condvar);
+            drop(guard);
+            preempt();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Try to decrement semaphore count.
+    ///
+    /// Returns `true` on success, `false` when count is zero.
+    pub fn try_take<C: Context>(&self, _cx: &mut C) -> bool {
+        let mut guard = self.value.lock().unwrap();
+        if *guard > 0 {
+            *guard -= 1;
+            notify_all(&self.condvar);
+            drop(guard);
+            preempt();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Increment semaphore count, waiting for it to become less than maximal.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns `true` on success, `false` when timed out.
+    pub fn give<C: BlockingContext>(&self, _cx: &mut C, timeout: Option<Duration>) -> bool {
+        let guard = self.value.lock().unwrap();
+        let (mut guard, done) = wait_while(&self.condvar, &self.value, guard, timeout, |value| {
+            *value >= self.max_count
+        });
+        if done {
+            *guard += 1;
+            notify_all(&self.condvar);
+            drop(guard);
+            preempt();
+        }
+        done
+    }
+
+    /// Decrement semaphore count, waiting for it to become non-zero.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns `true` on success, `false` when timed out.
+    pub fn take<C: BlockingContext>(&self, _cx: &mut C, timeout: Option<Duration>) -> bool {
+        let guard = self.value.lock().unwrap();
+        let (mut guard, done) = wait_while(&self.condvar, &self.value, guard, timeout, |value| {
+            *value == 0
+        });
+        if done {
+            *guard -= 1;
+            notify_all(&self.condvar);
+            drop(guard);
+            preempt();
+        }
+        done
+    }
+}
+
+/// Bounded multi-producer multi-consumer queue.
+pub struct Queue<T> {
+    items: StdMutex<VecDeque<T>>,
+    capacity: usize,
+    not_empty: StdCondvar,
+    not_full: StdCondvar,
+}
+
+impl<T> Queue<T> {
+    /// Create empty queue that can hold up to `capacity` items.
+    pub fn new(capacity: usize) -> Result<Self, Error> {
+        if capacity == 0 {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+        Ok(Self {
+            items: StdMutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            not_empty: StdCondvar::new(),
+            not_full: StdCondvar::new(),
+        })
+    }
+
+    /// Maximum number of items in queue.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Try to push `item` to the back of queue.
+    ///
+    /// Returns `item` back when queue is full.
+    pub fn try_send<C: Context>(&self, _cx: &mut C, item: T) -> Result<(), T> {
+        let mut guard = self.items.lock().unwrap();
+        if guard.len() < self.capacity {
+            guard.push_back(item);
+            notify_one(&self.not_empty);
+            drop(guard);
+            preempt();
+            Ok(())
+        } else {
+            Err(item)
+        }
+    }
+
+    /// Try to pop item from the front of queue.
+    ///
+    /// Returns `None` when queue is empty.
+    pub fn try_recv<C: Context>(&self, _cx: &mut C) -> Option<T> {
+        let item = self.items.lock().unwrap().pop_front();
+        if item.is_some() {
+            notify_one(&self.not_full);
+            preempt();
+        }
+        item
+    }
+
+    /// Push `item` to the back of queue, waiting for free space.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns `item` back when timed out.
+    pub fn send<C: BlockingContext>(
+        &self,
+        _cx: &mut C,
+        item: T,
+        timeout: Option<Duration>,
+    ) -> Result<(), T> {
+        let guard = self.items.lock().unwrap();
+        let (mut guard, done) = wait_while(&self.not_full, &self.items, guard, timeout, |items| {
+            items.len() >= self.capacity
+        });
+        if done {
+            guard.push_back(item);
+            notify_one(&self.not_empty);
+            drop(guard);
+            preempt();
+            Ok(())
+        } else {
+            Err(item)
+        }
+    }
+
+    /// Pop item from the front of queue, waiting for it to appear.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns `None` when timed out.
+    pub fn recv<C: BlockingContext>(&self, _cx: &mut C, timeout: Option<Duration>) -> Option<T> {
+        let guard = self.items.lock().unwrap();
+        let (mut guard, done) = wait_while(&self.not_empty, &self.items, guard, timeout, |items| {
+            items.is_empty()
+        });
+        if done {
+            let item = guard.pop_front();
+            notify_one(&self.not_full);
+            drop(guard);
+            preempt();
+            item
+        } else {
+            None
+        }
+    }
+}
+
+#[doc(hidden)]
+pub type EventBits = u32;
+
+struct EventWaiter {
+    id: usize,
+    bits: EventBits,
+    wait_for_all: bool,
+    clear_on_exit: bool,
+    /// Value of bits at the moment condition is met.
+    result: Option<EventBits>,
+}
+
+fn is_met(value: EventBits, bits: EventBits, wait_for_all: bool) -> bool {
+    if wait_for_all {
+        value & bits == bits
+    } else {
+        value & bits != 0
+    }
+}
+
+#[derive(Default)]
+struct EventGroupState {
+    bits: EventBits,
+    waiters: Vec<EventWaiter>,
+    next_id: usize,
+}
+
+impl EventGroupState {
+    /// Set bits and resolve waiters the same way FreeRTOS does.
+    fn set_bits(&mut self, bits: EventBits) {
+        self.bits |= bits;
+        let mut clear = 0;
+        for waiter in self.waiters.iter_mut().filter(|w| w.result.is_none()) {
+            if is_met(self.bits, waiter.bits, waiter.wait_for_all) {
+                waiter.result = Some(self.bits);
+                if waiter.clear_on_exit {
+                    clear |= waiter.bits;
                 }
-                None => self.condvar.wait(guard).unwrap(),
-            });
+            }
+        }
+        self.bits &= !clear;
+    }
+}
+
+/// Event group emulating semantics of FreeRTOS one, see `ustd::sync::EventGroup`.
+#[doc(hidden)]
+pub struct RawEventGroup {
+    state: StdMutex<EventGroupState>,
+    condvar: StdCondvar,
+}
+
+impl RawEventGroup {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            state: StdMutex::new(EventGroupState::default()),
+            condvar: StdCondvar::new(),
+        })
+    }
+
+    pub fn set_bits<C: Context>(&self, _cx: &mut C, bits: EventBits) -> bool {
+        self.state.lock().unwrap().set_bits(bits);
+        notify_all(&self.condvar);
+        preempt();
+        true
+    }
+
+    pub fn clear_bits<C: Context>(&self, _cx: &mut C, bits: EventBits) -> bool {
+        self.state.lock().unwrap().bits &= !bits;
+        true
+    }
+
+    pub fn get_bits<C: Context>(&self, _cx: &mut C) -> EventBits {
+        self.state.lock().unwrap().bits
+    }
+
+    /// Register waiter and wait for it to be resolved.
+    fn wait_locked(
+        &self,
+        mut state: StdMutexGuard<'_, EventGroupState>,
+        bits: EventBits,
+        wait_for_all: bool,
+        clear_on_exit: bool,
+        timeout: Option<Duration>,
+    ) -> Option<EventBits> {
+        let id = state.next_id;
+        state.next_id = state.next_id.wrapping_add(1);
+        state.waiters.push(EventWaiter {
+            id,
+            bits,
+            wait_for_all,
+            clear_on_exit,
+            result: None,
+        });
+        let (mut state, _) = wait_while(&self.condvar, &self.state, state, timeout, |state| {
+            state
+                .waiters
+                .iter()
+                .any(|w| w.id == id && w.result.is_none())
+        });
+        let index = state.waiters.iter().position(|w| w.id == id).unwrap();
+        state.waiters.swap_remove(index).result
+    }
+
+    pub fn wait_bits<C: BlockingContext>(
+        &self,
+        _cx: &mut C,
+        bits: EventBits,
+        wait_for_all: bool,
+        clear_on_exit: bool,
+        timeout: Option<Duration>,
+    ) -> Option<EventBits> {
+        let mut state = self.state.lock().unwrap();
+        let value = state.bits;
+        if is_met(value, bits, wait_for_all) {
+            if clear_on_exit {
+                state.bits &= !bits;
+            }
+            return Some(value);
         }
+        self.wait_locked(state, bits, wait_for_all, clear_on_exit, timeout)
+    }
+
+    pub fn sync<C: BlockingContext>(
+        &self,
+        _cx: &mut C,
+        set: EventBits,
+        wait: EventBits,
+        timeout: Option<Duration>,
+    ) -> Option<EventBits> {
+        let mut state = self.state.lock().unwrap();
+        let value = state.bits | set;
+        state.set_bits(set);
+        notify_all(&self.condvar);
+        if value & wait == wait {
+            state.bits &= !wait;
+            drop(state);
+            preempt();
+            return Some(value);
+        }
+        self.wait_locked(state, wait, true, true, timeout)
     }
 }
 
@@ -92,16 +542,23 @@ impl<T> Mutex<T> {
         })
     }
 
+    fn lock_value(&self) -> Result<MutexGuard<'_, T>, Error> {
+        // Value may still be locked by a task entering `Condvar` wait.
+        match self.value.lock() {
+            Ok(guard) => Ok(MutexGuard {
+                guard: Some(guard),
+                mutex: self,
+            }),
+            Err(_) => {
+                self.sem.try_give_inner();
+                Err(Error::other("Poisoned mutex"))
+            }
+        }
+    }
+
     pub fn try_lock(&self, cx: &mut TaskContext) -> Result<Option<MutexGuard<'_, T>>, Error> {
         if self.sem.try_take(cx) {
-            match self.value.try_lock() {
-                Ok(guard) => Ok(Some(MutexGuard {
-                    guard: Some(guard),
-                    sem: &self.sem,
-                })),
-                Err(TryLockError::WouldBlock) => unreachable!(),
-                Err(TryLockError::Poisoned(_)) => Err(Error::other("Poisoned mutex")),
-            }
+            self.lock_value().map(Some)
         } else {
             Ok(None)
         }
@@ -112,14 +569,7 @@ impl<T> Mutex<T> {
         timeout: Option<Duration>,
     ) -> Result<MutexGuard<'_, T>, Error> {
         if self.sem.take(cx, timeout) {
-            match self.value.try_lock() {
-                Ok(guard) => Ok(MutexGuard {
-                    guard: Some(guard),
-                    sem: &self.sem,
-                }),
-                Err(TryLockError::WouldBlock) => unreachable!(),
-                Err(TryLockError::Poisoned(_)) => Err(Error::other("Poisoned mutex")),
-            }
+            self.lock_value()
         } else {
             Err(ErrorKind::TimedOut.into())
         }
@@ -128,7 +578,7 @@ impl<T> Mutex<T> {
 
 pub struct MutexGuard<'a, T> {
     guard: Option<StdMutexGuard<'a, T>>,
-    sem: &'a Semaphore,
+    mutex: &'a Mutex<T>,
 }
 
 impl<T> Deref for MutexGuard<'_, T> {
@@ -146,7 +596,82 @@ impl<T> DerefMut for MutexGuard<'_, T> {
 
 impl<T> Drop for MutexGuard<'_, T> {
     fn drop(&mut self) {
-        drop(self.guard.take().unwrap());
-        assert!(self.sem.try_give(&mut TaskContext::current().unwrap()));
+        // Inner guard is missing when it is taken by `Condvar`.
+        if let Some(guard) = self.guard.take() {
+            drop(guard);
+            assert!(self
+                .mutex
+                .sem
+                .try_give(&mut TaskContext::current().unwrap()));
+        }
+    }
+}
+
+/// Condition variable to be used with [`Mutex`].
+///
+/// Internally wraps [`std::sync::Condvar`].
+pub struct Condvar(StdCondvar);
+
+impl Condvar {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self(StdCondvar::new()))
+    }
+
+    /// Release `guard` and wait for notification, then lock the mutex again.
+    pub fn wait<'a, T>(
+        &self,
+        cx: &mut TaskContext,
+        guard: MutexGuard<'a, T>,
+    ) -> Result<MutexGuard<'a, T>, Error> {
+        self.wait_timeout(cx, guard, None).map(|(guard, _)| guard)
+    }
+
+    /// Release `guard` and wait for notification, then lock the mutex again.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns `true` in second element on notification, `false` when timed out.
+    pub fn wait_timeout<'a, T>(
+        &self,
+        cx: &mut TaskContext,
+        mut guard: MutexGuard<'a, T>,
+        timeout: Option<Duration>,
+    ) -> Result<(MutexGuard<'a, T>, bool), Error> {
+        let mutex = guard.mutex;
+        // Inner guard is kept until wait starts so that no notification is missed.
+        let value = guard.guard.take().unwrap();
+        drop(guard);
+        // Other tasks must not run until inner guard is released.
+        assert!(mutex.sem.try_give_inner());
+
+        let (value, notified) = wait(&self.0, &mutex.value, value, timeout)?;
+        drop(value);
+
+        Ok((mutex.lock(cx, None)?, notified))
+    }
+
+    /// Wait for notifications while `condition` is true.
+    pub fn wait_while<'a, T, F: FnMut(&mut T) -> bool>(
+        &self,
+        cx: &mut TaskContext,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: F,
+    ) -> Result<MutexGuard<'a, T>, Error> {
+        while condition(&mut guard) {
+            guard = self.wait(cx, guard)?;
+        }
+        Ok(guard)
+    }
+
+    /// Wake up one waiting task if any.
+    pub fn notify_one(&self, _cx: &mut TaskContext) {
+        notify_one(&self.0);
+        preempt();
+    }
+
+    /// Wake up all waiting tasks.
+    pub fn notify_all(&self, _cx: &mut TaskContext) {
+        notify_all(&self.0);
+        preempt();
     }
 }
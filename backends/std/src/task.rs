@@ -1,13 +1,28 @@
 extern crate std;
 
-use crate::error::Error;
-use core::{marker::PhantomData, time::Duration};
+#[cfg(feature = "single-core")]
+use crate::sched;
+use crate::{
+    error::Error,
+    sync::{notify_all, preempt, wait_while},
+};
+use core::{
+    any::Any,
+    cell::{Cell, UnsafeCell},
+    fmt,
+    marker::PhantomData,
+    mem::take,
+    ptr::{null_mut, NonNull},
+    time::Duration,
+};
 use std::{
+    boxed::Box,
     cell::RefCell,
+    panic::{catch_unwind, AssertUnwindSafe},
     sync::{Arc, Condvar, Mutex, Weak},
     thread::{self, Thread, ThreadId},
     thread_local,
-    time::Instant,
+    vec::Vec,
 };
 
 /// Basic execution context.
@@ -24,10 +39,21 @@ pub type TaskId = ThreadId;
 /// Task priority.
 pub type Priority = usize;
 
+/// Priority of tasks unless specified otherwise.
+const DEFAULT_PRIORITY: Priority = 1;
+
+#[derive(Default)]
+struct Notification {
+    value: u32,
+    pending: bool,
+}
+
 #[derive(Default)]
 struct State {
     condvar: Condvar,
     finished: Mutex<bool>,
+    notified: Condvar,
+    notification: Mutex<Notification>,
 }
 
 impl State {
@@ -35,48 +61,90 @@ impl State {
         let mut guard = self.finished.lock().unwrap();
         assert!(!*guard);
         *guard = true;
-        self.condvar.notify_all();
+        notify_all(&self.condvar);
     }
     fn wait_finished(&self, timeout: Option<Duration>) -> bool {
-        let mut guard_slot = Some(self.finished.lock().unwrap());
-        let instant = Instant::now();
-        loop {
-            let guard = guard_slot.take().unwrap();
-            if *guard {
-                break true;
-            }
-            guard_slot.replace(match timeout {
-                Some(total) => {
-                    let current = instant.elapsed();
-                    if current >= total {
-                        break false;
-                    }
-                    self.condvar.wait_timeout(guard, total - current).unwrap().0
-                }
-                None => self.condvar.wait(guard).unwrap(),
+        let guard = self.finished.lock().unwrap();
+        wait_while(&self.condvar, &self.finished, guard, timeout, |finished| {
+            !*finished
+        })
+        .1
+    }
+    fn notify(&self, value: u32, action: NotifyAction) {
+        let mut guard = self.notification.lock().unwrap();
+        match action {
+            NotifyAction::NoAction => (),
+            NotifyAction::SetBits => guard.value |= value,
+            NotifyAction::Increment => guard.value = guard.value.wrapping_add(1),
+            NotifyAction::Overwrite => guard.value = value,
+        }
+        guard.pending = true;
+        notify_all(&self.notified);
+    }
+    fn wait_notification(&self, clear_mask: u32, timeout: Option<Duration>) -> Option<u32> {
+        let guard = self.notification.lock().unwrap();
+        let (mut guard, done) =
+            wait_while(&self.notified, &self.notification, guard, timeout, |n| {
+                !n.pending
             });
+        if done {
+            let value = guard.value;
+            guard.value &= !clear_mask;
+            guard.pending = false;
+            Some(value)
+        } else {
+            None
         }
     }
 }
 
+/// How notification value is updated by [`Task::notify`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NotifyAction {
+    /// Leave value unchanged.
+    NoAction,
+    /// Bitwise OR value with given one.
+    SetBits,
+    /// Increment value, given one is ignored.
+    Increment,
+    /// Replace value with given one.
+    Overwrite,
+}
+
 /// Unit of execution.
 ///
 /// Internally the same as [`std::thread::Thread`].
 #[derive(Clone)]
 pub struct Task {
     thread: Thread,
+    state: Weak<State>,
 }
 
-impl From<Thread> for Task {
-    fn from(thread: Thread) -> Self {
-        Self { thread }
-    }
-}
-
-/// Task handle.
-pub struct Handle {
+/// Task handle that allows to wait for task to finish and get its result.
+pub struct JoinHandle<T> {
     task: Task,
     state: Weak<State>,
+    result: Arc<Mutex<Option<T>>>,
+    /// Result has already been taken or found missing.
+    joined: bool,
+}
+
+/// Error returned by [`JoinHandle::join`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum JoinError {
+    /// Task hasn't finished in time.
+    Timeout,
+    /// Task has finished without result because of panic.
+    Panicked,
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "Task join timed out"),
+            Self::Panicked => write!(f, "Task panicked"),
+        }
+    }
 }
 
 /// Context inside task.
@@ -88,6 +156,12 @@ pub struct TaskContext {
 }
 
 impl Task {
+    fn new(thread: Thread, state: &Arc<State>) -> Self {
+        Self {
+            thread,
+            state: Arc::downgrade(state),
+        }
+    }
     /// Task unique identifier.
     pub fn id(&self) -> TaskId {
         self.thread.id()
@@ -95,24 +169,72 @@ impl Task {
     pub fn thread(&self) -> Thread {
         self.thread.clone()
     }
+    /// Send notification to the task, updating its notification value according to `action`.
+    ///
+    /// Notification is discarded if the task has already finished.
+    pub fn notify<C: Context>(&self, _cx: &mut C, value: u32, action: NotifyAction) {
+        if let Some(state) = self.state.upgrade() {
+            state.notify(value, action);
+            drop(state);
+            preempt();
+        }
+    }
+}
+
+/// Notifications sent to the task created this way are discarded.
+impl From<Thread> for Task {
+    fn from(thread: Thread) -> Self {
+        Self {
+            thread,
+            state: Weak::new(),
+        }
+    }
 }
 
-impl Handle {
+impl<T> JoinHandle<T> {
     pub fn task(&self) -> Task {
         self.task.clone()
     }
     /// Wait for task to finish.
-    pub fn join<C: BlockingContext>(&self, _cx: &mut C, timeout: Option<Duration>) -> bool {
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns `true` when task is finished, `false` when timed out.
+    pub fn wait<C: BlockingContext>(&self, _cx: &mut C, timeout: Option<Duration>) -> bool {
         if let Some(state) = self.state.upgrade() {
             state.wait_finished(timeout)
         } else {
             true
         }
     }
+    /// Wait for task to finish and take its result.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    /// If timed out then the task continues to run and can be joined again.
+    ///
+    /// Panics if the task has already been joined successfully or found panicked.
+    pub fn join<C: BlockingContext>(
+        &mut self,
+        cx: &mut C,
+        timeout: Option<Duration>,
+    ) -> Result<T, JoinError> {
+        assert!(!self.joined, "Task has already been joined");
+        if self.wait(cx, timeout) {
+            self.joined = true;
+            self.result
+                .lock()
+                .unwrap()
+                .take()
+                .ok_or(JoinError::Panicked)
+        } else {
+            Err(JoinError::Timeout)
+        }
+    }
 }
 
 thread_local! {
     static STATE: RefCell<Weak<State>> = const { RefCell::new(Weak::new()) };
+    static LOCALS: Cell<*mut Locals> = const { Cell::new(null_mut()) };
 }
 
 fn init_current_state(state: Arc<State>) {
@@ -122,6 +244,36 @@ fn init_current_state(state: Arc<State>) {
     });
 }
 
+/// Values of task locals indexed by key, see `ustd::task::TaskLocal`.
+///
+/// Values are boxed and stored as raw pointers, so that references to them stay valid while locals are modified.
+#[doc(hidden)]
+pub type Locals = Vec<Option<NonNull<dyn Any>>>;
+
+/// Task locals of current task, `None` if the task isn't spawned by ustd.
+///
+/// The pointer is valid until the task finishes and must be accessed only by the task itself.
+#[doc(hidden)]
+pub fn current_locals() -> Option<*mut Locals> {
+    let locals = LOCALS.get();
+    (!locals.is_null()).then_some(locals)
+}
+
+/// Drop task locals.
+///
+/// Values accessed by destructors of other values are dropped too.
+fn clear_locals(locals: &UnsafeCell<Locals>) {
+    loop {
+        let values = take(unsafe { &mut *locals.get() });
+        if values.is_empty() {
+            break;
+        }
+        for value in values.into_iter().flatten() {
+            drop(unsafe { Box::from_raw(value.as_ptr()) });
+        }
+    }
+}
+
 impl TaskContext {
     fn new(task: Task, state: Arc<State>) -> Self {
         Self {
@@ -134,21 +286,34 @@ impl TaskContext {
     ///
     /// Panics if context for the task already exists.
     pub fn enter() -> Self {
+        #[cfg(feature = "single-core")]
+        sched::enter(DEFAULT_PRIORITY);
         let state = Arc::new(State::default());
         init_current_state(state.clone());
-        Self::new(thread::current().into(), state)
+        Self::new(Task::new(thread::current(), &state), state)
     }
     /// Get already created context for current task.
     ///
     /// Returns `None` if context hasn't created or already dropped.
     pub fn current() -> Option<Self> {
         let state = STATE.with_borrow(|weak| weak.upgrade())?;
-        Some(Self::new(thread::current().into(), state))
+        Some(Self::new(Task::new(thread::current(), &state), state))
     }
 
     pub fn task(&self) -> Task {
         self.task.clone()
     }
+
+    /// Wait for notification sent by [`Task::notify`] to current task.
+    ///
+    /// When notification is received then bits of `clear_mask` are cleared in notification value.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns notification value (before clearing), `None` when timed out.
+    pub fn wait_notification(&mut self, clear_mask: u32, timeout: Option<Duration>) -> Option<u32> {
+        self.state.wait_notification(clear_mask, timeout)
+    }
 }
 
 impl Context for TaskContext {}
@@ -158,6 +323,10 @@ impl BlockingContext for TaskContext {
     ///
     /// If `None` then sleep infinetely.
     fn sleep(&mut self, duration: Option<Duration>) {
+        #[cfg(feature = "single-core")]
+        if sched::current().is_some() {
+            return sched::sleep(duration);
+        }
         match duration {
             Some(t) => thread::sleep(t),
             None => loop {
@@ -190,6 +359,9 @@ impl Context for InterruptContext {}
 
 pub struct Builder {
     inner: thread::Builder,
+    /// Used only in single-core mode.
+    #[cfg_attr(not(feature = "single-core"), allow(dead_code))]
+    priority: Priority,
 }
 
 impl Builder {
@@ -197,13 +369,13 @@ impl Builder {
     pub fn new() -> Self {
         Self {
             inner: thread::Builder::new(),
+            priority: DEFAULT_PRIORITY,
         }
     }
 
-    fn map<F: FnOnce(thread::Builder) -> thread::Builder>(self, f: F) -> Self {
-        Self {
-            inner: f(self.inner),
-        }
+    fn map<F: FnOnce(thread::Builder) -> thread::Builder>(mut self, f: F) -> Self {
+        self.inner = f(self.inner);
+        self
     }
 
     pub fn name(self, name: &str) -> Self {
@@ -212,35 +384,59 @@ impl Builder {
     pub fn stack_size(self, size: usize) -> Self {
         self.map(|b| b.stack_size(size))
     }
-    pub fn priority(self, _: Priority) -> Self {
-        // nothing to do
-        self
+    pub fn priority(self, priority: Priority) -> Self {
+        Self { priority, ..self }
     }
-    pub fn spawn<F: FnOnce(&mut TaskContext) + Send + 'static>(
+    pub fn spawn<T: Send + 'static, F: FnOnce(&mut TaskContext) -> T + Send + 'static>(
         self,
         func: F,
-    ) -> Result<Handle, Error> {
+    ) -> Result<JoinHandle<T>, Error> {
         let state = Arc::new(State::default());
-        let thread = {
+        let result = Arc::new(Mutex::new(None));
+        #[cfg(feature = "single-core")]
+        let id = sched::create(self.priority);
+        let spawned = {
             let state = state.clone();
-            self.inner
-                .spawn(move || {
-                    init_current_state(state.clone());
-                    let mut cx = TaskContext::new(thread::current().into(), state);
-                    func(&mut cx);
-                    cx.state.finish();
-                })?
-                .thread()
-                .clone()
+            let result = result.clone();
+            self.inner.spawn(move || {
+                #[cfg(feature = "single-core")]
+                sched::start(id);
+                init_current_state(state.clone());
+                let locals = UnsafeCell::new(Locals::new());
+                LOCALS.set(locals.get());
+                let mut cx = TaskContext::new(Task::new(thread::current(), &state), state);
+                // Panic is reported by `JoinHandle::join` as missing result.
+                if let Ok(value) = catch_unwind(AssertUnwindSafe(|| func(&mut cx))) {
+                    result.lock().unwrap().replace(value);
+                }
+                let _ = catch_unwind(AssertUnwindSafe(|| clear_locals(&locals)));
+                LOCALS.set(null_mut());
+                cx.state.finish();
+                #[cfg(feature = "single-core")]
+                sched::exit();
+            })
+        };
+        let thread = match spawned {
+            Ok(handle) => handle.thread().clone(),
+            Err(err) => {
+                #[cfg(feature = "single-core")]
+                sched::remove(id);
+                return Err(err);
+            }
         };
-        Ok(Handle {
-            task: Task { thread },
+        preempt();
+        Ok(JoinHandle {
+            task: Task::new(thread, &state),
             state: Arc::downgrade(&state),
+            result,
+            joined: false,
         })
     }
 }
 
 /// Spawn a new task.
-pub fn spawn<F: FnOnce(&mut TaskContext) + Send + 'static>(func: F) -> Result<Handle, Error> {
+pub fn spawn<T: Send + 'static, F: FnOnce(&mut TaskContext) -> T + Send + 'static>(
+    func: F,
+) -> Result<JoinHandle<T>, Error> {
     Builder::new().spawn(func)
 }
@@ -0,0 +1,108 @@
+//! Backend part of test runner, see `ustd::test`.
+
+extern crate std;
+
+use crate::{
+    process::exit,
+    task::{JoinError, TaskContext, TaskId},
+};
+use core::time::Duration;
+use std::{
+    boxed::Box,
+    env,
+    panic::{self, PanicHookInfo},
+    string::{String, ToString},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Mutex, Once, PoisonError, RwLock,
+    },
+    thread,
+    vec::Vec,
+};
+
+/// Runner arguments passed in command line, `None` if there are no arguments.
+#[doc(hidden)]
+pub fn args() -> Option<String> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    (!args.is_empty()).then(|| args.join(" "))
+}
+
+/// Messages of panicked tasks.
+static PANICS: Mutex<Vec<(TaskId, String)>> = Mutex::new(Vec::new());
+
+/// Record panic messages in addition to default panic handling.
+fn install_panic_hook() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info: &PanicHookInfo| {
+            let payload = info.payload();
+            let message = match payload.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => payload
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .unwrap_or_default(),
+            };
+            PANICS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((thread::current().id(), message));
+            default_hook(info);
+        }));
+    });
+}
+
+/// Take recorded panic message of task.
+#[doc(hidden)]
+pub fn take_panic(task: TaskId) -> Option<String> {
+    let mut panics = PANICS.lock().unwrap_or_else(PoisonError::into_inner);
+    let index = panics.iter().position(|(id, _)| *id == task)?;
+    Some(panics.swap_remove(index).1)
+}
+
+/// Run test runner `func` in current thread and exit with returned code.
+#[doc(hidden)]
+pub fn run<F: FnOnce(&mut TaskContext) -> i32 + Send + 'static>(func: F) {
+    install_panic_hook();
+    let mut cx = TaskContext::enter();
+    exit(func(&mut cx));
+}
+
+/// Held exclusively by sequential tests and shared by others.
+static EXCLUSIVE: RwLock<()> = RwLock::new(());
+
+/// Run `func` in a new thread named `name` while current thread watches it.
+///
+/// When `sequential` is `true` then no other watched threads run at the same time.
+///
+/// Returns [`JoinError::Timeout`] if the thread hasn't finished within `timeout`, the thread is left running.
+#[doc(hidden)]
+pub fn watch<R: Send + 'static, F: FnOnce() -> R + Send + 'static>(
+    name: &str,
+    timeout: Duration,
+    sequential: bool,
+    func: F,
+) -> Result<R, JoinError> {
+    let _shared;
+    let _exclusive;
+    if sequential {
+        _exclusive = EXCLUSIVE.write().unwrap_or_else(PoisonError::into_inner);
+    } else {
+        _shared = EXCLUSIVE.read().unwrap_or_else(PoisonError::into_inner);
+    }
+    install_panic_hook();
+    let (sender, receiver) = mpsc::channel();
+    thread::Builder::new()
+        .name(name.into())
+        .spawn(move || {
+            let _ = sender.send(func());
+        })
+        .unwrap();
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => Ok(result),
+        // Sender is dropped without sending when `func` panics.
+        Err(RecvTimeoutError::Disconnected) => Err(JoinError::Panicked),
+        Err(RecvTimeoutError::Timeout) => Err(JoinError::Timeout),
+    }
+}
@@ -1,7 +1,5 @@
 extern crate std;
 
-pub use std::time::Instant;
-
 use std::{
     marker::PhantomData,
     ops::ControlFlow,
@@ -10,10 +8,49 @@ use std::{
         Arc,
     },
     thread::{self, sleep, Thread},
-    time::Duration,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "single-core")]
+use crate::{sched, task::Priority};
+use crate::{
+    task::{BlockingContext, Context},
+    Error,
 };
 
-use crate::{task::Context, Error};
+/// Point in time of monotonic clock, see `ustd::time::Instant`.
+#[doc(hidden)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct RawInstant(Instant);
+
+impl RawInstant {
+    pub fn now(_cx: &mut impl Context) -> Self {
+        Self(Instant::now())
+    }
+
+    pub fn checked_duration_since(&self, earlier: Self) -> Option<Duration> {
+        self.0.checked_duration_since(earlier.0)
+    }
+    pub fn checked_add(&self, duration: Duration) -> Option<Self> {
+        self.0.checked_add(duration).map(Self)
+    }
+    pub fn checked_sub(&self, duration: Duration) -> Option<Self> {
+        self.0.checked_sub(duration).map(Self)
+    }
+}
+
+/// Block current task until `deadline`, see `ustd::time::sleep_until`.
+#[doc(hidden)]
+pub fn sleep_until(_cx: &mut impl BlockingContext, deadline: RawInstant) {
+    #[cfg(feature = "single-core")]
+    if sched::current().is_some() {
+        return sched::sleep_until(Some(deadline.0));
+    }
+    let now = Instant::now();
+    if deadline.0 > now {
+        sleep(deadline.0 - now);
+    }
+}
 
 #[derive(Default)]
 struct TimerState {
@@ -70,7 +107,12 @@ impl TimerBuilder {
                         if state.stopped.load(Ordering::Acquire) {
                             break;
                         }
-                        match f(&mut cx) {
+                        #[cfg(feature = "single-core")]
+                        sched::enter(Priority::MAX);
+                        let flow = f(&mut cx);
+                        #[cfg(feature = "single-core")]
+                        sched::exit();
+                        match flow {
                             ControlFlow::Break(()) => break,
                             ControlFlow::Continue(new_period_or_same) => match new_period_or_same {
                                 None => (),
@@ -1,12 +1,11 @@
 use core::time::Duration;
-use macro_rules_attribute::apply;
 use ustd::task::BlockingContext;
 
-#[apply(ustd::main)]
+#[ustd::attr::main]
 fn main(cx: &mut ustd::task::TaskContext) {
     println!("Main task: {:?}", cx.task().id(),);
     cx.sleep(Some(Duration::from_millis(100)));
-    let handle = ustd::task::Builder::new()
+    let mut handle = ustd::task::Builder::new()
         .priority(2)
         .spawn(|cx| {
             println!("Spawned task (inside): {:?}", cx.task().id(),);
@@ -14,5 +13,5 @@ fn main(cx: &mut ustd::task::TaskContext) {
         .unwrap();
     println!("Spawned task (outside): {:?}", handle.task().id(),);
 
-    handle.join(cx, None);
+    handle.join(cx, None).unwrap();
 }
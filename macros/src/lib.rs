@@ -0,0 +1,293 @@
+//! Procedural macros of [`ustd`](https://docs.rs/ustd), use them through `ustd::attr` re-exports.
+//!
+//! Generated code refers to `ustd` as `::ustd`, it can be overridden with `crate = path` option
+//! when `ustd` is renamed or re-exported by another crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::{
+    meta::ParseNestedMeta, parse_macro_input, spanned::Spanned, Block, Error, Expr, FnArg, Ident,
+    ItemFn, Pat, Path, ReturnType, Type,
+};
+
+/// Entry point of ustd program.
+///
+/// Function takes `&mut TaskContext` and is run as the main task.
+/// It may return any `ustd::process::Termination` value, e.g. `Result<(), E>` or `ExitCode`,
+/// then failure is reported and the program exits with its code.
+///
+/// Options:
+///
+/// + `name = "..."` - name of the main task, `"main"` by default,
+/// + `stack_size = ...` - stack size of the main task,
+/// + `priority = ...` - priority of the main task,
+/// + `init = ...` - function to call before the main task is started (and before the scheduler is started on FreeRTOS),
+/// + `crate = ...` - path to `ustd` crate.
+///
+/// ```ignore
+/// #[ustd::attr::main(stack_size = 4096, priority = 2)]
+/// fn main(cx: &mut ustd::task::TaskContext) {
+///     // ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn main(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut name = None;
+    let mut stack_size = None;
+    let mut priority = None;
+    let mut init = None;
+    let mut krate = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("crate") {
+            set_crate(&meta, &mut krate)
+        } else if meta.path.is_ident("name") {
+            set_option(&meta, &mut name)
+        } else if meta.path.is_ident("stack_size") {
+            set_option(&meta, &mut stack_size)
+        } else if meta.path.is_ident("priority") {
+            set_option(&meta, &mut priority)
+        } else if meta.path.is_ident("init") {
+            set_option(&meta, &mut init)
+        } else {
+            Err(meta.error(
+                "unsupported option, expected `name`, `stack_size`, `priority`, `init` or `crate`",
+            ))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let item = parse_macro_input!(item as ItemFn);
+
+    let func = match TaskFn::parse(&item) {
+        Ok(func) => func,
+        Err(err) => return err.into_compile_error().into(),
+    };
+    let ItemFn { attrs, vis, .. } = &item;
+    let TaskFn { ident, cx, .. } = &func;
+    let inner = func.inner();
+    let krate = crate_path(krate);
+    let call = match func.output {
+        None => quote! { #ident(#cx) },
+        // Errors about unsupported return type point to the type.
+        Some(ty) => quote_spanned! { ty.span()=>
+            #krate::process::Termination::report(#ident(#cx))
+        },
+    };
+    let name = name.unwrap_or_else(|| quote! { "main" });
+    let stack_size = stack_size.map(|size| quote! { .stack_size(#size) });
+    let priority = priority.map(|priority| quote! { .priority(#priority) });
+    let init = match init {
+        Some(init) => quote! { ::core::option::Option::Some(#init) },
+        None => quote! { ::core::option::Option::None },
+    };
+    quote! {
+        #( #attrs )*
+        #vis fn #ident() {
+            #inner
+            #krate::task::run_main(
+                #krate::task::Builder::new().name(#name) #stack_size #priority,
+                #init,
+                |#cx: &mut #krate::task::TaskContext| #call,
+            );
+        }
+    }
+    .into()
+}
+
+/// Declare test, the same as `ustd::test!`.
+///
+/// Function takes `&mut TaskContext` and is run in a separate task.
+/// It may return `Result<(), E>` where `E: Debug`, then error fails the test.
+///
+/// Options:
+///
+/// + `timeout = ...` - time limit of test as [`Duration`](core::time::Duration),
+/// + `mode = Sequential | Parallel` - whether test may run concurrently with other tests,
+/// + `crate = ...` - path to `ustd` crate.
+///
+/// `#[ignore]` and `#[should_panic]` attributes are also supported.
+///
+/// ```ignore
+/// #[ustd::attr::test(timeout = Duration::from_secs(10))]
+/// fn ping_pong(cx: &mut ustd::task::TaskContext) {
+///     // ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut timeout = None;
+    let mut mode = None;
+    let mut krate = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("crate") {
+            set_crate(&meta, &mut krate)
+        } else if meta.path.is_ident("timeout") {
+            set_option(&meta, &mut timeout)
+        } else if meta.path.is_ident("mode") {
+            if mode.is_some() {
+                return Err(meta.error("duplicate option"));
+            }
+            let value: Ident = meta.value()?.parse()?;
+            if value != "Sequential" && value != "Parallel" {
+                return Err(Error::new(
+                    value.span(),
+                    "unknown mode, expected `Sequential` or `Parallel`",
+                ));
+            }
+            mode = Some(value);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported option, expected `timeout`, `mode` or `crate`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let item = parse_macro_input!(item as ItemFn);
+
+    let func = match TaskFn::parse(&item) {
+        Ok(func) => func,
+        Err(err) => return err.into_compile_error().into(),
+    };
+    let ItemFn { attrs, vis, .. } = &item;
+    let TaskFn {
+        ident, cx, cx_ty, ..
+    } = &func;
+    let timeout = timeout.map(|timeout| quote! { #[timeout(#timeout)] });
+    let mode = mode.map(|mode| quote! { #[mode(#mode)] });
+    let body = if func.output.is_some() {
+        let inner = func.inner();
+        let call = func.call();
+        quote! {{
+            #inner
+            #call
+        }}
+    } else {
+        let block = &func.block;
+        quote! { #block }
+    };
+    let krate = crate_path(krate);
+    quote! {
+        #krate::test! {
+            #timeout
+            #mode
+            #( #attrs )*
+            #vis fn #ident(#cx: #cx_ty) #body
+        }
+    }
+    .into()
+}
+
+/// Store option value, reporting duplicates.
+fn set_option(meta: &ParseNestedMeta, option: &mut Option<TokenStream2>) -> syn::Result<()> {
+    if option.is_some() {
+        return Err(meta.error("duplicate option"));
+    }
+    let value: Expr = meta.value()?.parse()?;
+    *option = Some(quote! { #value });
+    Ok(())
+}
+
+/// Store path to `ustd` crate, reporting duplicates.
+fn set_crate(meta: &ParseNestedMeta, krate: &mut Option<Path>) -> syn::Result<()> {
+    if krate.is_some() {
+        return Err(meta.error("duplicate option"));
+    }
+    *krate = Some(meta.value()?.parse()?);
+    Ok(())
+}
+
+/// Path to `ustd` crate, `::ustd` unless overridden.
+fn crate_path(krate: Option<Path>) -> TokenStream2 {
+    match krate {
+        Some(path) => quote! { #path },
+        None => quote! { ::ustd },
+    }
+}
+
+/// Function taking task context.
+struct TaskFn<'a> {
+    ident: &'a Ident,
+    cx: &'a Ident,
+    cx_ty: &'a Type,
+    /// Return type unless it is `()`.
+    output: Option<&'a Type>,
+    block: &'a Block,
+}
+
+impl<'a> TaskFn<'a> {
+    fn parse(item: &'a ItemFn) -> syn::Result<Self> {
+        let sig = &item.sig;
+        if let Some(constness) = &sig.constness {
+            return Err(Error::new(constness.span(), "function must not be `const`"));
+        }
+        if let Some(asyncness) = &sig.asyncness {
+            return Err(Error::new(asyncness.span(), "function must not be `async`"));
+        }
+        if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+            return Err(Error::new(
+                sig.generics.span(),
+                "function must not be generic",
+            ));
+        }
+        if let Some(variadic) = &sig.variadic {
+            return Err(Error::new(variadic.span(), "function must not be variadic"));
+        }
+        let expected = "expected single `cx: &mut TaskContext` argument";
+        let mut inputs = sig.inputs.iter();
+        let (arg, None) = (inputs.next(), inputs.next()) else {
+            return Err(Error::new(sig.inputs.span(), expected));
+        };
+        let Some(FnArg::Typed(arg)) = arg else {
+            let span = arg.map_or(sig.paren_token.span.join(), |arg| arg.span());
+            return Err(Error::new(span, expected));
+        };
+        let Pat::Ident(pat) = &*arg.pat else {
+            return Err(Error::new(arg.pat.span(), "argument must be an identifier"));
+        };
+        let output = match &sig.output {
+            ReturnType::Default => None,
+            ReturnType::Type(_, ty) => match &**ty {
+                Type::Tuple(tuple) if tuple.elems.is_empty() => None,
+                ty => Some(ty),
+            },
+        };
+        Ok(Self {
+            ident: &sig.ident,
+            cx: &pat.ident,
+            cx_ty: &arg.ty,
+            output,
+            block: &item.block,
+        })
+    }
+
+    /// Original function.
+    fn inner(&self) -> TokenStream2 {
+        let Self {
+            ident,
+            cx,
+            cx_ty,
+            output,
+            block,
+        } = self;
+        let output = output.map(|ty| quote! { -> #ty });
+        quote! {
+            fn #ident(#cx: #cx_ty) #output #block
+        }
+    }
+
+    /// Call of original function with error reporting.
+    fn call(&self) -> TokenStream2 {
+        let Self { ident, cx, .. } = self;
+        match self.output {
+            None => quote! { #ident(#cx) },
+            Some(ty) => {
+                // Errors about unsupported return type point to the type.
+                quote_spanned! { ty.span()=>
+                    match #ident(#cx) {
+                        ::core::result::Result::Ok(()) => (),
+                        ::core::result::Result::Err(err) => ::core::panic!("Error: {:?}", err),
+                    }
+                }
+            }
+        }
+    }
+}
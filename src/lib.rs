@@ -1,7 +1,27 @@
 #![no_std]
 
+extern crate alloc;
+
+#[cfg(feature = "std")]
+use backend_std as backend;
 #[cfg(feature = "std")]
 pub use backend_std::*;
 
+#[cfg(feature = "freertos")]
+use backend_freertos as backend;
 #[cfg(feature = "freertos")]
 pub use backend_freertos::*;
+
+pub mod process;
+pub mod sync;
+pub mod task;
+pub mod test;
+pub mod time;
+
+/// Attribute macros, alternative to [`main!`] and [`test!`] that doesn't require `macro_rules_attribute`.
+pub mod attr {
+    pub use macros::{main, test};
+}
+
+#[doc(hidden)]
+pub use linkme;
@@ -0,0 +1,56 @@
+//! Program termination.
+
+pub use crate::backend::process::exit;
+
+use crate::println;
+use core::fmt::Debug;
+
+/// Status code of program termination.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ExitCode(u8);
+
+impl ExitCode {
+    pub const SUCCESS: Self = Self(0);
+    pub const FAILURE: Self = Self(1);
+
+    /// Terminate the whole program with this code.
+    pub fn exit_process(self) -> ! {
+        exit(self.0.into())
+    }
+}
+
+impl From<u8> for ExitCode {
+    fn from(code: u8) -> Self {
+        Self(code)
+    }
+}
+
+/// Value that can be returned from main task.
+pub trait Termination {
+    /// Convert the value to exit code, reporting error if any.
+    fn report(self) -> ExitCode;
+}
+
+impl Termination for () {
+    fn report(self) -> ExitCode {
+        ExitCode::SUCCESS
+    }
+}
+
+impl Termination for ExitCode {
+    fn report(self) -> ExitCode {
+        self
+    }
+}
+
+impl<T: Termination, E: Debug> Termination for Result<T, E> {
+    fn report(self) -> ExitCode {
+        match self {
+            Ok(value) => value.report(),
+            Err(err) => {
+                println!("Error: {:?}", err);
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
@@ -0,0 +1,271 @@
+pub use crate::backend::sync::*;
+
+use crate::{
+    backend::error,
+    task::{BlockingContext, Context, TaskContext},
+    time::Instant,
+    Error,
+};
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    time::Duration,
+};
+
+#[derive(Default)]
+struct RwLockState {
+    readers: usize,
+    writer: bool,
+    waiting_writers: usize,
+}
+
+/// Reader-writer lock.
+///
+/// Built from [`Mutex`] and [`Condvar`].
+///
+/// The lock prefers writers: once a writer is waiting, new readers are blocked until it acquires
+/// and releases the lock. So a task must not acquire read lock recursively.
+pub struct RwLock<T> {
+    state: Mutex<RwLockState>,
+    condvar: Condvar,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    pub fn new(value: T) -> Result<Self, Error> {
+        Ok(Self {
+            state: Mutex::new(RwLockState::default())?,
+            condvar: Condvar::new()?,
+            value: UnsafeCell::new(value),
+        })
+    }
+
+    /// Wait until lock can be acquired for reading or writing and acquire it.
+    ///
+    /// Returns `false` when timed out.
+    fn acquire(
+        &self,
+        cx: &mut TaskContext,
+        write: bool,
+        timeout: Option<Duration>,
+    ) -> Result<bool, Error> {
+        let start = Instant::now(cx);
+        let mut state = self.state.lock(cx, None)?;
+        if write {
+            state.waiting_writers += 1;
+        }
+        let acquired = loop {
+            let available = if write {
+                !state.writer && state.readers == 0
+            } else {
+                !state.writer && state.waiting_writers == 0
+            };
+            if available {
+                break true;
+            }
+            let remaining = match timeout {
+                Some(t) => match t.checked_sub(start.elapsed(cx)) {
+                    Some(r) if !r.is_zero() => Some(r),
+                    _ => break false,
+                },
+                None => None,
+            };
+            state = self.condvar.wait_timeout(cx, state, remaining)?.0;
+        };
+        if write {
+            state.waiting_writers -= 1;
+            if acquired {
+                state.writer = true;
+            } else {
+                // Readers blocked by this writer may proceed.
+                drop(state);
+                self.condvar.notify_all(cx);
+            }
+        } else if acquired {
+            state.readers += 1;
+        }
+        Ok(acquired)
+    }
+
+    pub fn try_read(&self, cx: &mut TaskContext) -> Result<Option<RwLockReadGuard<'_, T>>, Error> {
+        Ok(self
+            .acquire(cx, false, Some(Duration::ZERO))?
+            .then(|| RwLockReadGuard { lock: self }))
+    }
+    pub fn read(
+        &self,
+        cx: &mut TaskContext,
+        timeout: Option<Duration>,
+    ) -> Result<RwLockReadGuard<'_, T>, Error> {
+        if self.acquire(cx, false, timeout)? {
+            Ok(RwLockReadGuard { lock: self })
+        } else {
+            Err(error::timeout())
+        }
+    }
+
+    pub fn try_write(
+        &self,
+        cx: &mut TaskContext,
+    ) -> Result<Option<RwLockWriteGuard<'_, T>>, Error> {
+        Ok(self
+            .acquire(cx, true, Some(Duration::ZERO))?
+            .then(|| RwLockWriteGuard { lock: self }))
+    }
+    pub fn write(
+        &self,
+        cx: &mut TaskContext,
+        timeout: Option<Duration>,
+    ) -> Result<RwLockWriteGuard<'_, T>, Error> {
+        if self.acquire(cx, true, timeout)? {
+            Ok(RwLockWriteGuard { lock: self })
+        } else {
+            Err(error::timeout())
+        }
+    }
+}
+
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut cx = TaskContext::current().unwrap();
+        let mut state = self.lock.state.lock(&mut cx, None).unwrap();
+        state.readers -= 1;
+        if state.readers == 0 {
+            drop(state);
+            self.lock.condvar.notify_all(&mut cx);
+        }
+    }
+}
+
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut cx = TaskContext::current().unwrap();
+        self.lock.state.lock(&mut cx, None).unwrap().writer = false;
+        self.lock.condvar.notify_all(&mut cx);
+    }
+}
+
+/// Set of event bits.
+///
+/// Has the same width as tick type of the backend.
+pub type EventBits = crate::backend::sync::EventBits;
+
+/// Bits of [`EventBits`] that can be used in [`EventGroup`].
+///
+/// The upper 8 bits are reserved by FreeRTOS.
+pub const EVENT_BITS_MASK: EventBits = EventBits::MAX >> 8;
+
+/// Condition of [`EventGroup::wait_bits`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WaitMode {
+    /// Wait for any of bits to be set.
+    Any,
+    /// Wait for all bits to be set.
+    All,
+}
+
+/// Group of event bits that tasks can wait for.
+///
+/// Wraps native event group in `freertos` backend, where setting and clearing bits from interrupt context is deferred to timer task.
+pub struct EventGroup {
+    raw: RawEventGroup,
+}
+
+impl EventGroup {
+    /// Create event group with all bits cleared.
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            raw: RawEventGroup::new()?,
+        })
+    }
+
+    /// Set `bits` and wake up tasks which conditions are met.
+    ///
+    /// Returns `true` on success, `false` when request cannot be deferred from interrupt context.
+    pub fn set_bits<C: Context>(&self, cx: &mut C, bits: EventBits) -> bool {
+        assert_eq!(bits & !EVENT_BITS_MASK, 0);
+        self.raw.set_bits(cx, bits)
+    }
+
+    /// Clear `bits`.
+    ///
+    /// Returns `true` on success, `false` when request cannot be deferred from interrupt context.
+    pub fn clear_bits<C: Context>(&self, cx: &mut C, bits: EventBits) -> bool {
+        assert_eq!(bits & !EVENT_BITS_MASK, 0);
+        self.raw.clear_bits(cx, bits)
+    }
+
+    /// Current value of bits.
+    pub fn get_bits<C: Context>(&self, cx: &mut C) -> EventBits {
+        self.raw.get_bits(cx)
+    }
+
+    /// Wait for any or all of `bits` to be set.
+    ///
+    /// When `clear_on_exit` is `true` then `bits` are cleared if condition is met.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns value of bits at the moment condition is met (before clearing), `None` when timed out.
+    pub fn wait_bits<C: BlockingContext>(
+        &self,
+        cx: &mut C,
+        bits: EventBits,
+        mode: WaitMode,
+        clear_on_exit: bool,
+        timeout: Option<Duration>,
+    ) -> Option<EventBits> {
+        assert!(bits != 0 && bits & !EVENT_BITS_MASK == 0);
+        self.raw
+            .wait_bits(cx, bits, mode == WaitMode::All, clear_on_exit, timeout)
+    }
+
+    /// Rendezvous: atomically set `set` bits and wait for all of `wait` bits to be set.
+    ///
+    /// On success `wait` bits are cleared.
+    ///
+    /// When `timeout` is `None` then wait infinitely.
+    ///
+    /// Returns value of bits at the moment condition is met (before clearing), `None` when timed out.
+    pub fn sync<C: BlockingContext>(
+        &self,
+        cx: &mut C,
+        set: EventBits,
+        wait: EventBits,
+        timeout: Option<Duration>,
+    ) -> Option<EventBits> {
+        assert!(wait != 0 && (set | wait) & !EVENT_BITS_MASK == 0);
+        self.raw.sync(cx, set, wait, timeout)
+    }
+}
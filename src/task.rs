@@ -0,0 +1,232 @@
+pub use crate::backend::task::*;
+
+use crate::process::{ExitCode, Termination};
+use alloc::boxed::Box;
+use core::{
+    any::Any,
+    fmt,
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Key of task local storage.
+///
+/// Value is lazily initialized on first access in each task and dropped when the task finishes.
+/// Task local storage is available only in tasks spawned by ustd.
+///
+/// Should be declared using [`task_local!`](crate::task_local) macro.
+pub struct TaskLocal<T: 'static> {
+    init: fn() -> T,
+    /// Index of value in task locals plus one, or zero if not assigned yet.
+    index: AtomicUsize,
+}
+
+/// Error returned by [`TaskLocal::try_with`] when current task isn't spawned by ustd.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AccessError;
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Task locals are available only in tasks spawned by ustd")
+    }
+}
+
+impl<T: 'static> TaskLocal<T> {
+    #[doc(hidden)]
+    pub const fn new(init: fn() -> T) -> Self {
+        Self {
+            init,
+            index: AtomicUsize::new(0),
+        }
+    }
+
+    fn index(&self) -> usize {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let index = self.index.load(Ordering::Acquire);
+        if index != 0 {
+            return index - 1;
+        }
+        let new = COUNTER.fetch_add(1, Ordering::Relaxed) + 1;
+        match self
+            .index
+            .compare_exchange(0, new, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => new - 1,
+            Err(index) => index - 1,
+        }
+    }
+
+    /// Access value of current task.
+    ///
+    /// Panics if current task isn't spawned by ustd.
+    pub fn with<R, F: FnOnce(&T) -> R>(&'static self, cx: &mut TaskContext, f: F) -> R {
+        match self.try_with(cx, f) {
+            Ok(result) => result,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Access value of current task, fails if current task isn't spawned by ustd.
+    pub fn try_with<R, F: FnOnce(&T) -> R>(
+        &'static self,
+        _cx: &mut TaskContext,
+        f: F,
+    ) -> Result<R, AccessError> {
+        let index = self.index();
+        let locals = current_locals().ok_or(AccessError)?;
+        let value = match unsafe { get_local::<T>(locals, index) } {
+            Some(value) => value,
+            // Value is initialized before locals are accessed because `init` may access other task locals.
+            None => unsafe { insert_local(locals, index, Box::new((self.init)())) },
+        };
+        // Value isn't moved or dropped until the task finishes.
+        Ok(f(unsafe { value.as_ref() }))
+    }
+}
+
+/// Get stored value.
+///
+/// Locals are borrowed only during the call, so that references to values are unaffected by modification of locals.
+///
+/// # Safety
+/// `locals` must be task locals of current task.
+unsafe fn get_local<T: 'static>(locals: *mut Locals, index: usize) -> Option<NonNull<T>> {
+    let value = (&*locals).get(index).copied().flatten()?;
+    Some(NonNull::from(value.as_ref().downcast_ref::<T>().unwrap()))
+}
+
+/// Store `value` unless it has been already stored (by recursive access from `init`).
+///
+/// Returns pointer to stored value.
+///
+/// # Safety
+/// `locals` must be task locals of current task.
+unsafe fn insert_local<T: 'static>(locals: *mut Locals, index: usize, value: Box<T>) -> NonNull<T> {
+    if let Some(stored) = get_local(locals, index) {
+        return stored;
+    }
+    let value = NonNull::from(Box::leak(value));
+    let locals = &mut *locals;
+    if locals.len() <= index {
+        locals.resize(index + 1, None);
+    }
+    locals[index] = Some(value as NonNull<dyn Any>);
+    value
+}
+
+/// Declare task local storage keys of type [`TaskLocal`].
+#[macro_export]
+macro_rules! task_local {
+    ($( $( #[$attr:meta] )* $vis:vis static $name:ident: $ty:ty = $init:expr );* $(;)?) => {
+        $(
+            $( #[$attr] )*
+            $vis static $name: $crate::task::TaskLocal<$ty> = $crate::task::TaskLocal::new({
+                fn init() -> $ty {
+                    $init
+                }
+                init
+            });
+        )*
+    };
+}
+
+/// Run `func` in the main task configured by `builder` and wait for it to finish.
+///
+/// `init` is called before the main task is started.
+/// The program exits with the code reported by result of `func`.
+///
+/// Used by [`main!`](crate::main) and [`attr::main`](crate::attr::main).
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub fn run_main<T: Termination, F: FnOnce(&mut TaskContext) -> T + Send + 'static>(
+    builder: Builder,
+    init: Option<fn()>,
+    func: F,
+) {
+    if let Some(init) = init {
+        init();
+    }
+    let mut handle = match builder.spawn(move |cx| func(cx).report()) {
+        Ok(handle) => handle,
+        Err(err) => Err::<(), _>(err).report().exit_process(),
+    };
+    let mut cx = TaskContext::enter();
+    match handle.join(&mut cx, None) {
+        Ok(ExitCode::SUCCESS) => (),
+        Ok(code) => code.exit_process(),
+        Err(_) => panic!("Main task panicked"),
+    }
+}
+
+/// Spawn `func` in the main task configured by `builder` and start scheduler.
+///
+/// `init` is called before the scheduler is started.
+/// If result of `func` reports failure then the program exits with its code,
+/// otherwise other tasks continue to run.
+///
+/// Used by [`main!`](crate::main) and [`attr::main`](crate::attr::main).
+#[cfg(feature = "freertos")]
+#[doc(hidden)]
+pub fn run_main<T: Termination, F: FnOnce(&mut TaskContext) -> T + Send + 'static>(
+    builder: Builder,
+    init: Option<fn()>,
+    func: F,
+) {
+    if let Some(init) = init {
+        init();
+    }
+    let spawned = builder.spawn(move |cx| {
+        let code = func(cx).report();
+        if code != ExitCode::SUCCESS {
+            code.exit_process();
+        }
+    });
+    if let Err(err) = spawned {
+        Err::<(), _>(err).report().exit_process();
+    }
+    crate::freertos::FreeRtosUtils::start_scheduler();
+}
+
+/// Entry point of ustd program.
+///
+/// The function is run in the main task and may return any [`Termination`](crate::process::Termination) value,
+/// e.g. `Result<(), E>` or [`ExitCode`](crate::process::ExitCode).
+/// Failure is reported and the program exits with its code.
+///
+/// The main task can be configured by attributes:
+///
+/// + `#[name(..)]` - task name, `"main"` by default,
+/// + `#[stack_size(..)]` - task stack size,
+/// + `#[priority(..)]` - task priority,
+/// + `#[init(..)]` - function to call before the main task is started (and before the scheduler is started on FreeRTOS).
+#[macro_export]
+macro_rules! main {
+    (@parse [$( $attr:tt )*] [$( $method:ident($value:expr) )*] [$init:expr] #[name($name:expr)] $( $rest:tt )*) => {
+        $crate::main!(@parse [$( $attr )*] [$( $method($value) )* name($name)] [$init] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $method:ident($value:expr) )*] [$init:expr] #[stack_size($size:expr)] $( $rest:tt )*) => {
+        $crate::main!(@parse [$( $attr )*] [$( $method($value) )* stack_size($size)] [$init] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $method:ident($value:expr) )*] [$init:expr] #[priority($priority:expr)] $( $rest:tt )*) => {
+        $crate::main!(@parse [$( $attr )*] [$( $method($value) )* priority($priority)] [$init] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $method:ident($value:expr) )*] [$init:expr] #[init($func:expr)] $( $rest:tt )*) => {
+        $crate::main!(@parse [$( $attr )*] [$( $method($value) )*] [Some($func)] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $method:ident($value:expr) )*] [$init:expr] #[$meta:meta] $( $rest:tt )*) => {
+        $crate::main!(@parse [$( $attr )* #[$meta]] [$( $method($value) )*] [$init] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $method:ident($value:expr) )*] [$init:expr] $vis:vis fn $name:ident($cx:ident: $cx_ty:ty) $( -> $ret:ty )? $body:block) => {
+        $( $attr )*
+        $vis fn $name() {
+            $crate::task::run_main(
+                $crate::task::Builder::new().name("main") $( .$method($value) )*,
+                $init,
+                |$cx: $cx_ty| $( -> $ret )? { $body },
+            );
+        }
+    };
+    ($( $input:tt )*) => {
+        $crate::main!(@parse [] [] [None] $( $input )*);
+    };
+}
@@ -0,0 +1,507 @@
+//! Test runner.
+//!
+//! Tests declared by [`test!`](crate::test!) are run in a single program by [`tests_main!`](crate::tests_main) with [`run_tests`]
+//! or, with `std` backend, by `cargo test` harness one by one with `run_test`.
+//!
+//! Arguments of [`run_tests`] are whitespace-separated test name filters (a test is run if its name contains any of them)
+//! and flags:
+//!
+//! + `--exact` - a test is run only if its name is equal to one of filters,
+//! + `--ignored` - run only ignored tests,
+//! + `--include-ignored` - run ignored tests too.
+//!
+//! Arguments are taken from command line with `std` backend
+//! and from `__ustd_test_args()` glue function (that returns null-terminated string or `NULL`) with FreeRTOS backend.
+//! When there are no arguments then `USTD_TEST_FILTER` environment variable at compile time is used instead.
+
+use crate::{
+    backend, println,
+    task::{self, JoinError, JoinHandle, TaskContext},
+    time::Instant,
+};
+use alloc::{string::String, vec::Vec};
+use core::time::Duration;
+use linkme::distributed_slice;
+
+/// Timeout of test unless specified otherwise.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How test is run relative to other tests.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    /// Run test alone, after all previous tests have finished.
+    Sequential,
+    /// Run test together with adjacent parallel tests.
+    Parallel,
+}
+
+/// Whether test is expected to panic.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShouldPanic {
+    No,
+    Yes,
+    /// Panic message must contain the string.
+    YesWithMessage(&'static str),
+}
+
+impl ShouldPanic {
+    /// Check test outcome given panic message if it has panicked.
+    ///
+    /// Returns reason of failure if the outcome isn't expected.
+    fn check(self, panic: Option<&str>) -> Result<(), &'static str> {
+        match (self, panic) {
+            (Self::No, None) => Ok(()),
+            (Self::No, Some(_)) => Err("panicked"),
+            (_, None) => Err("test did not panic as expected"),
+            (Self::Yes, Some(_)) => Ok(()),
+            (Self::YesWithMessage(expected), Some(message)) => {
+                if message.contains(expected) {
+                    Ok(())
+                } else {
+                    Err("panic did not contain expected string")
+                }
+            }
+        }
+    }
+}
+
+/// Test declared by [`test!`](crate::test!).
+pub struct Test {
+    /// Full path of test function.
+    pub name: &'static str,
+    pub func: fn(&mut TaskContext),
+    pub timeout: Duration,
+    /// Mode of test, the runner mode is used if `None`.
+    pub mode: Option<Mode>,
+    /// Test isn't run unless ignored tests are requested.
+    pub ignore: bool,
+    pub should_panic: ShouldPanic,
+}
+
+impl Test {
+    /// Test with default settings.
+    pub const fn new(name: &'static str, func: fn(&mut TaskContext)) -> Self {
+        Self {
+            name,
+            func,
+            timeout: DEFAULT_TIMEOUT,
+            mode: None,
+            ignore: false,
+            should_panic: ShouldPanic::No,
+        }
+    }
+
+    /// Path of test function without crate name.
+    fn short_name(&self) -> &'static str {
+        self.name
+            .split_once("::")
+            .map_or(self.name, |(_, name)| name)
+    }
+}
+
+/// All tests declared by [`test!`](crate::test!) in the program.
+#[distributed_slice]
+pub static TESTS: [Test] = [..];
+
+/// Run test in its own task and wait for it to finish.
+///
+/// Returns panic message if the test has panicked.
+#[cfg(feature = "std")]
+fn run_task(cx: &mut TaskContext, test: &Test) -> Option<String> {
+    let mut handle = task::Builder::new()
+        .name(test.short_name())
+        .spawn(test.func)
+        .unwrap();
+    let task = handle.task().id();
+    match handle.join(cx, None) {
+        Ok(()) => None,
+        Err(_) => Some(backend::test::take_panic(task).unwrap_or_default()),
+    }
+}
+
+/// Run test in a separate thread and panic if it fails or doesn't finish in time.
+///
+/// Harness thread acts as a watchdog, so the test is failed even if it hangs.
+/// Tests are run by `cargo test` harness in parallel unless they are [`Mode::Sequential`].
+#[cfg(feature = "std")]
+pub fn run_test(test: &'static Test) {
+    let sequential = test.mode == Some(Mode::Sequential);
+    let result = backend::test::watch(test.name, test.timeout, sequential, move || {
+        run_task(&mut TaskContext::enter(), test)
+    });
+    let panic = match result {
+        Ok(panic) => panic,
+        Err(JoinError::Panicked) => panic!("Test runner panicked"),
+        Err(JoinError::Timeout) => panic!("Test timed out after {:?}", test.timeout),
+    };
+    if let Err(reason) = test.should_panic.check(panic.as_deref()) {
+        panic!("Test failed: {}", reason);
+    }
+}
+
+struct Filter<'a> {
+    patterns: Vec<&'a str>,
+    exact: bool,
+    ignored: bool,
+    include_ignored: bool,
+}
+
+impl<'a> Filter<'a> {
+    fn parse(args: &'a str) -> Self {
+        let mut filter = Self {
+            patterns: Vec::new(),
+            exact: false,
+            ignored: false,
+            include_ignored: false,
+        };
+        for arg in args.split_whitespace() {
+            match arg {
+                "--exact" => filter.exact = true,
+                "--ignored" => filter.ignored = true,
+                "--include-ignored" => filter.include_ignored = true,
+                _ => filter.patterns.push(arg),
+            }
+        }
+        filter
+    }
+
+    fn matches(&self, test: &Test) -> bool {
+        let name = test.short_name();
+        (!self.ignored || test.ignore)
+            && (self.patterns.is_empty()
+                || self.patterns.iter().any(|pattern| {
+                    if self.exact {
+                        name == *pattern
+                    } else {
+                        name.contains(pattern)
+                    }
+                }))
+    }
+
+    fn skips(&self, test: &Test) -> bool {
+        test.ignore && !self.ignored && !self.include_ignored
+    }
+}
+
+struct Running {
+    test: &'static Test,
+    handle: JoinHandle<()>,
+    start: Instant,
+}
+
+/// Runs tests and reports their results.
+#[derive(Default)]
+struct Supervisor {
+    passed: usize,
+    ignored: usize,
+    failed: Vec<&'static str>,
+    running: Vec<Running>,
+    timed_out: Vec<Running>,
+}
+
+impl Supervisor {
+    fn spawn(&mut self, cx: &mut TaskContext, test: &'static Test) {
+        let handle = task::Builder::new()
+            .name(test.short_name())
+            .spawn(test.func)
+            .unwrap();
+        self.running.push(Running {
+            test,
+            handle,
+            start: Instant::now(cx),
+        });
+    }
+
+    /// Wait for all running tests to finish or time out.
+    ///
+    /// Tests are joined in order of their deadlines, so the supervisor wakes up only when the test
+    /// it waits for finishes or times out.
+    fn wait_all(&mut self, cx: &mut TaskContext) {
+        while let Some((index, remaining)) = self
+            .running
+            .iter()
+            .map(|running| {
+                running
+                    .test
+                    .timeout
+                    .saturating_sub(running.start.elapsed(cx))
+            })
+            .enumerate()
+            .min_by_key(|(_, remaining)| *remaining)
+        {
+            let mut running = self.running.remove(index);
+            let name = running.test.short_name();
+            let task = running.handle.task().id();
+            let panic = match running.handle.join(cx, Some(remaining)) {
+                Ok(()) => None,
+                Err(JoinError::Panicked) => {
+                    Some(backend::test::take_panic(task).unwrap_or_default())
+                }
+                Err(JoinError::Timeout) => {
+                    println!(
+                        "test {} ... FAILED (timed out after {:?})",
+                        name, running.test.timeout
+                    );
+                    self.failed.push(name);
+                    self.timed_out.push(running);
+                    self.print_still_running(cx);
+                    continue;
+                }
+            };
+            match running.test.should_panic.check(panic.as_deref()) {
+                Ok(()) => {
+                    println!("test {} ... ok", name);
+                    self.passed += 1;
+                }
+                Err(reason) => {
+                    println!("test {} ... FAILED ({})", name, reason);
+                    self.failed.push(name);
+                }
+            }
+        }
+    }
+
+    /// Print tests that haven't finished yet, including timed out ones.
+    fn print_still_running(&self, cx: &mut TaskContext) {
+        let still_running = self
+            .timed_out
+            .iter()
+            .chain(&self.running)
+            .filter(|running| !running.handle.wait(cx, Some(Duration::ZERO)))
+            .collect::<Vec<_>>();
+        if !still_running.is_empty() {
+            println!("still running:");
+            for running in still_running {
+                println!("    {}", running.test.short_name());
+            }
+        }
+    }
+}
+
+/// Run tests, each in its own task, and exit with non-zero code if any of them failed.
+///
+/// Tests are run in order of their names according to their [`Mode`], `mode` is used for tests that don't specify it.
+/// Tests are filtered by runner arguments or by `filter` if there are no arguments.
+///
+/// Runner task supervises tests: panicked tasks are reported as failed instead of aborting the program,
+/// tests that haven't finished in time are reported as failed and left running.
+/// Panic messages are recorded to check them against [`ShouldPanic::YesWithMessage`].
+pub fn run_tests(tests: &'static [Test], mode: Mode, filter: Option<&'static str>) {
+    let args = backend::test::args();
+    backend::test::run(move |cx| {
+        let filter = Filter::parse(args.as_deref().or(filter).unwrap_or(""));
+        let mut selected = tests
+            .iter()
+            .filter(|test| filter.matches(test))
+            .collect::<Vec<_>>();
+        selected.sort_by_key(|test| test.name);
+        let filtered_out = tests.len() - selected.len();
+        println!("running {} tests", selected.len());
+
+        let mut supervisor = Supervisor::default();
+        for test in selected {
+            if filter.skips(test) {
+                println!("test {} ... ignored", test.short_name());
+                supervisor.ignored += 1;
+                continue;
+            }
+            let sequential = test.mode.unwrap_or(mode) == Mode::Sequential;
+            if sequential {
+                supervisor.wait_all(cx);
+            }
+            supervisor.spawn(cx, test);
+            if sequential {
+                supervisor.wait_all(cx);
+            }
+        }
+        supervisor.wait_all(cx);
+        println!();
+        if !supervisor.timed_out.is_empty() {
+            supervisor.print_still_running(cx);
+            println!();
+        }
+        if supervisor.failed.is_empty() {
+            println!(
+                "test result: ok. {} passed; 0 failed; {} ignored; {} filtered out",
+                supervisor.passed, supervisor.ignored, filtered_out
+            );
+            0
+        } else {
+            println!("failures:");
+            for name in &supervisor.failed {
+                println!("    {}", name);
+            }
+            println!();
+            println!(
+                "test result: FAILED. {} passed; {} failed; {} ignored; {} filtered out",
+                supervisor.passed,
+                supervisor.failed.len(),
+                supervisor.ignored,
+                filtered_out
+            );
+            1
+        }
+    });
+}
+
+/// Declare test to be run by `cargo test` harness and [`tests_main!`](crate::tests_main).
+///
+/// Test fails if it doesn't finish within [`DEFAULT_TIMEOUT`](crate::test::DEFAULT_TIMEOUT),
+/// which can be overridden by `#[timeout(duration)]` attribute.
+/// Test marked with `#[mode(Sequential)]` attribute doesn't run concurrently with other tests.
+/// `#[ignore]` and `#[should_panic]` (optionally with `expected = "message"`) attributes are supported as in Rust tests.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! test {
+    (@parse [$( $attr:tt )*] [$( $test_attr:tt )*] [$( $field:ident = $value:expr; )*] #[timeout($timeout:expr)] $( $rest:tt )*) => {
+        $crate::test!(@parse [$( $attr )*] [$( $test_attr )*] [$( $field = $value; )* timeout = $timeout;] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $test_attr:tt )*] [$( $field:ident = $value:expr; )*] #[mode($mode:ident)] $( $rest:tt )*) => {
+        $crate::test!(@parse [$( $attr )*] [$( $test_attr )*] [$( $field = $value; )* mode = Some($crate::test::Mode::$mode);] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $test_attr:tt )*] [$( $field:ident = $value:expr; )*] #[ignore $( = $reason:literal )?] $( $rest:tt )*) => {
+        $crate::test!(@parse [$( $attr )*] [$( $test_attr )* #[ignore $( = $reason )?]] [$( $field = $value; )* ignore = true;] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $test_attr:tt )*] [$( $field:ident = $value:expr; )*] #[should_panic] $( $rest:tt )*) => {
+        $crate::test!(@parse [$( $attr )*] [$( $test_attr )*] [$( $field = $value; )* should_panic = $crate::test::ShouldPanic::Yes;] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $test_attr:tt )*] [$( $field:ident = $value:expr; )*] #[should_panic(expected = $message:literal)] $( $rest:tt )*) => {
+        $crate::test!(@parse [$( $attr )*] [$( $test_attr )*] [$( $field = $value; )* should_panic = $crate::test::ShouldPanic::YesWithMessage($message);] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $test_attr:tt )*] [$( $field:ident = $value:expr; )*] #[$meta:meta] $( $rest:tt )*) => {
+        $crate::test!(@parse [$( $attr )* #[$meta]] [$( $test_attr )*] [$( $field = $value; )*] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $test_attr:tt )*] [$( $field:ident = $value:expr; )*] $vis:vis fn $name:ident($cx:ident: $cx_ty:ty) $body:block) => {
+        $( $attr )*
+        #[cfg(not(test))]
+        $vis fn $name($cx: $cx_ty) $body
+
+        $( $attr )*
+        #[cfg(not(test))]
+        pub mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            pub const TEST: $crate::test::Test = {
+                #[allow(unused_mut)]
+                let mut test = $crate::test::Test::new(module_path!(), super::$name);
+                $( test.$field = $value; )*
+                test
+            };
+
+            #[$crate::linkme::distributed_slice($crate::test::TESTS)]
+            #[linkme(crate = $crate::linkme)]
+            static REGISTERED: $crate::test::Test = TEST;
+        }
+
+        $( $attr )*
+        #[cfg(test)]
+        #[::core::prelude::v1::test]
+        $( $test_attr )*
+        $vis fn $name() {
+            fn $name($cx: $cx_ty) $body
+            static TEST: $crate::test::Test = {
+                #[allow(unused_mut)]
+                let mut test = $crate::test::Test::new(concat!(module_path!(), "::", stringify!($name)), $name);
+                $( test.$field = $value; )*
+                test
+            };
+            $crate::test::run_test(&TEST);
+        }
+    };
+    ($( $input:tt )*) => {
+        $crate::test!(@parse [] [] [] $( $input )*);
+    };
+}
+
+/// Declare test to be run by [`tests_main!`](crate::tests_main).
+///
+/// Test fails if it doesn't finish within [`DEFAULT_TIMEOUT`](crate::test::DEFAULT_TIMEOUT),
+/// which can be overridden by `#[timeout(duration)]` attribute.
+/// Test is run in the runner [`Mode`](crate::test::Mode) unless it is overridden by `#[mode(Sequential)]` or `#[mode(Parallel)]` attribute.
+/// `#[ignore]` and `#[should_panic]` (optionally with `expected = "message"`) attributes are supported as in Rust tests.
+#[cfg(feature = "freertos")]
+#[macro_export]
+macro_rules! test {
+    (@parse [$( $attr:tt )*] [$( $field:ident = $value:expr; )*] #[timeout($timeout:expr)] $( $rest:tt )*) => {
+        $crate::test!(@parse [$( $attr )*] [$( $field = $value; )* timeout = $timeout;] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $field:ident = $value:expr; )*] #[mode($mode:ident)] $( $rest:tt )*) => {
+        $crate::test!(@parse [$( $attr )*] [$( $field = $value; )* mode = Some($crate::test::Mode::$mode);] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $field:ident = $value:expr; )*] #[ignore $( = $reason:literal )?] $( $rest:tt )*) => {
+        $crate::test!(@parse [$( $attr )*] [$( $field = $value; )* ignore = true;] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $field:ident = $value:expr; )*] #[should_panic] $( $rest:tt )*) => {
+        $crate::test!(@parse [$( $attr )*] [$( $field = $value; )* should_panic = $crate::test::ShouldPanic::Yes;] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $field:ident = $value:expr; )*] #[should_panic(expected = $message:literal)] $( $rest:tt )*) => {
+        $crate::test!(@parse [$( $attr )*] [$( $field = $value; )* should_panic = $crate::test::ShouldPanic::YesWithMessage($message);] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $field:ident = $value:expr; )*] #[$meta:meta] $( $rest:tt )*) => {
+        $crate::test!(@parse [$( $attr )* #[$meta]] [$( $field = $value; )*] $( $rest )*);
+    };
+    (@parse [$( $attr:tt )*] [$( $field:ident = $value:expr; )*] $vis:vis fn $name:ident($cx:ident: $cx_ty:ty) $body:block) => {
+        $( $attr )*
+        $vis fn $name($cx: $cx_ty) $body
+
+        $( $attr )*
+        pub mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            pub const TEST: $crate::test::Test = {
+                #[allow(unused_mut)]
+                let mut test = $crate::test::Test::new(module_path!(), super::$name);
+                $( test.$field = $value; )*
+                test
+            };
+
+            #[$crate::linkme::distributed_slice($crate::test::TESTS)]
+            #[linkme(crate = $crate::linkme)]
+            static REGISTERED: $crate::test::Test = TEST;
+        }
+    };
+    ($( $input:tt )*) => {
+        $crate::test!(@parse [] [] $( $input )*);
+    };
+}
+
+/// Run listed tests declared by [`test!`](crate::test!) in a single program with [`run_tests`].
+///
+/// When no tests are listed then all tests registered in [`TESTS`](crate::test::TESTS) are run.
+///
+/// Tests are run in parallel unless runner mode is specified as `mode = Sequential` (followed by `;` and the list of tests if any).
+/// See [`test`](mod@crate::test) module for how to filter tests.
+///
+/// In `cargo test` build the tests are run by the test harness instead.
+#[macro_export]
+macro_rules! tests_main {
+    (mode = $mode:ident) => {
+        #[cfg(not(test))]
+        fn main() {
+            $crate::test::run_tests(
+                &$crate::test::TESTS,
+                $crate::test::Mode::$mode,
+                option_env!("USTD_TEST_FILTER"),
+            );
+        }
+    };
+    (mode = $mode:ident; $( $( #[$meta:meta] )* $( $test:ident )::+ ),* $(,)?) => {
+        #[cfg(not(test))]
+        fn main() {
+            static TESTS: &[$crate::test::Test] = &[$( $( #[$meta] )* $( $test )::+::TEST ),*];
+            $crate::test::run_tests(
+                TESTS,
+                $crate::test::Mode::$mode,
+                option_env!("USTD_TEST_FILTER"),
+            );
+        }
+    };
+    () => {
+        $crate::tests_main!(mode = Parallel);
+    };
+    ($( $( #[$meta:meta] )* $( $test:ident )::+ ),* $(,)?) => {
+        $crate::tests_main!(mode = Parallel; $( $( #[$meta] )* $( $test )::+ ),*);
+    };
+}
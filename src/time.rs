@@ -0,0 +1,178 @@
+pub use crate::backend::time::*;
+
+use crate::task::{BlockingContext, Context};
+use core::{
+    ops::{Add, AddAssign, Sub, SubAssign},
+    time::Duration,
+};
+
+/// Point in time of monotonic clock.
+///
+/// On FreeRTOS the clock is kernel tick counter, so durations are rounded to whole ticks.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Instant(RawInstant);
+
+impl Instant {
+    pub fn now(cx: &mut impl Context) -> Self {
+        Self(RawInstant::now(cx))
+    }
+    /// Time passed since `self`.
+    pub fn elapsed(&self, cx: &mut impl Context) -> Duration {
+        Self::now(cx).saturating_duration_since(*self)
+    }
+
+    /// Time passed from `earlier` to `self`, zero if `earlier` is later than `self`.
+    ///
+    /// Saturates as `std::time::Instant::duration_since` does since Rust 1.60
+    /// (it panicked before and may panic again in the future),
+    /// use [`Self::checked_duration_since`] to detect that `earlier` is later than `self`.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.saturating_duration_since(earlier)
+    }
+    /// Time passed from `earlier` to `self`, `None` if `earlier` is later than `self`.
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.0.checked_duration_since(earlier.0)
+    }
+    /// Time passed from `earlier` to `self`, zero if `earlier` is later than `self`.
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or_default()
+    }
+
+    /// `None` if the result cannot be represented.
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_add(duration).map(Self)
+    }
+    /// `None` if the result cannot be represented.
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_sub(duration).map(Self)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+    /// # Panics
+    /// When the result cannot be represented.
+    fn add(self, duration: Duration) -> Instant {
+        self.checked_add(duration)
+            .expect("overflow when adding duration to instant")
+    }
+}
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, duration: Duration) {
+        *self = *self + duration;
+    }
+}
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+    /// # Panics
+    /// When the result cannot be represented.
+    fn sub(self, duration: Duration) -> Instant {
+        self.checked_sub(duration)
+            .expect("overflow when subtracting duration from instant")
+    }
+}
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, duration: Duration) {
+        *self = *self - duration;
+    }
+}
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+    /// Same as [`Instant::duration_since`].
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+/// Block current task until `deadline`, return immediately if it has already passed.
+///
+/// Unlike [`BlockingContext::sleep`] the wake time doesn't depend on the moment of call.
+pub fn sleep_until(cx: &mut impl BlockingContext, deadline: Instant) {
+    crate::backend::time::sleep_until(cx, deadline.0)
+}
+
+/// Behavior of [`Interval`] when ticks are missed because the task has been late.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum MissedTickBehavior {
+    /// Fire missed ticks immediately one after another to catch up with the original schedule.
+    #[default]
+    Burst,
+    /// Schedule next tick a period after the moment the late tick has fired.
+    Delay,
+    /// Drop missed ticks and fire next tick at the original schedule.
+    Skip,
+}
+
+/// Ticks at fixed rate regardless of how long the task runs between them.
+///
+/// Unlike repeated [`sleep`](BlockingContext::sleep) calls, the schedule doesn't drift.
+#[derive(Debug)]
+pub struct Interval {
+    /// `None` if the next tick is too far to be represented, so it never fires.
+    next: Option<Instant>,
+    period: Duration,
+    missed_tick_behavior: MissedTickBehavior,
+}
+
+impl Interval {
+    /// Interval with the first tick firing immediately.
+    ///
+    /// # Panics
+    /// When `period` is zero.
+    pub fn new(cx: &mut impl Context, period: Duration) -> Self {
+        Self::new_at(Instant::now(cx), period)
+    }
+    /// Interval with the first tick firing at `start`.
+    ///
+    /// # Panics
+    /// When `period` is zero.
+    pub fn new_at(start: Instant, period: Duration) -> Self {
+        assert!(!period.is_zero(), "interval period must be non-zero");
+        Self {
+            next: Some(start),
+            period,
+            missed_tick_behavior: MissedTickBehavior::default(),
+        }
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.missed_tick_behavior
+    }
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.missed_tick_behavior = behavior;
+    }
+
+    /// Wait until the next tick.
+    ///
+    /// Returns the number of whole periods the tick is late by, that is the number of ticks missed so far.
+    /// Blocks forever if the next tick is too far to be represented.
+    pub fn tick(&mut self, cx: &mut impl BlockingContext) -> u32 {
+        let next = match self.next {
+            Some(next) => next,
+            None => loop {
+                cx.sleep(None);
+            },
+        };
+        sleep_until(cx, next);
+        let now = Instant::now(cx);
+        let late = now.saturating_duration_since(next);
+        let missed = u32::try_from(late.as_nanos() / self.period.as_nanos()).unwrap_or(u32::MAX);
+        self.next = match self.missed_tick_behavior {
+            MissedTickBehavior::Burst => next.checked_add(self.period),
+            MissedTickBehavior::Delay => now.checked_add(self.period),
+            MissedTickBehavior::Skip => self
+                .period
+                .checked_mul(missed.saturating_add(1))
+                .and_then(|skipped| next.checked_add(skipped)),
+        };
+        missed
+    }
+
+    /// Schedule the next tick a period from now.
+    pub fn reset(&mut self, cx: &mut impl Context) {
+        self.next = Instant::now(cx).checked_add(self.period);
+    }
+}
@@ -4,6 +4,9 @@ git submodule init && \
 cd freertos-rust && git submodule update --init freertos-rust-examples/FreeRTOS-Kernel && cd .. && \
 cd tests && \
 cargo test --lib --no-default-features --features=std && \
+cargo run --no-default-features --features=std && \
+cargo test --lib --no-default-features --features=single-core && \
+cargo run --no-default-features --features=single-core && \
 cargo run --no-default-features --features=freertos && \
 echo "" && \
 echo "Success!"
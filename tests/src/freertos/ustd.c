@@ -1,6 +1,10 @@
+#include <stdint.h>
 #include <stdio.h>
 #include <stdlib.h>
 
+#include "FreeRTOS.h"
+#include "task.h"
+
 char __ustd_io_buffer[0x100];
 
 size_t __ustd_io_buffer_size = sizeof(__ustd_io_buffer);
@@ -13,6 +17,21 @@ void __ustd_panic() {
 	abort();
 }
 
-void __ustd_exit() {
-	exit(0);
+void __ustd_exit_code(int code) {
+	exit(code);
+}
+
+const char *__ustd_test_args() {
+	return getenv("USTD_TEST_ARGS");
+}
+
+uint32_t __ustd_tick_rate_hz() {
+	return configTICK_RATE_HZ;
+}
+
+BaseType_t __ustd_tick_overflow_count() {
+	TimeOut_t timeout;
+	// Only reads the overflow count, so it's safe to call from interrupts.
+	vTaskInternalSetTimeOutState(&timeout);
+	return timeout.xOverflowCount;
 }
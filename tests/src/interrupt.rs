@@ -0,0 +1,98 @@
+//! Interrupts are simulated by creating [`InterruptContext`] in a task.
+
+extern crate alloc;
+
+use alloc::sync::Arc;
+use core::time::Duration;
+use ustd::{
+    sync::{EventGroup, Queue, Semaphore, WaitMode},
+    task::{self, BlockingContext, InterruptContext, TaskContext},
+    time::Instant,
+};
+
+const BIG_TIMEOUT: Option<Duration> = Some(Duration::from_secs(1));
+
+#[ustd::attr::test]
+fn semaphore(cx: &mut TaskContext) {
+    let sem = Arc::new(Semaphore::new().unwrap());
+
+    let mut task = task::spawn({
+        let sem = sem.clone();
+        move |cx| assert!(sem.take(cx, BIG_TIMEOUT))
+    })
+    .unwrap();
+
+    {
+        let mut icx = unsafe { InterruptContext::new() };
+        assert!(sem.try_give(&mut icx));
+        assert!(!sem.try_give(&mut icx));
+        assert!(sem.try_take(&mut icx));
+        assert!(!sem.try_take(&mut icx));
+        assert!(sem.try_give(&mut icx));
+    }
+    task.join(cx, BIG_TIMEOUT).unwrap();
+}
+
+#[ustd::attr::test]
+fn queue(cx: &mut TaskContext) {
+    let queue = Arc::new(Queue::<usize>::new(2).unwrap());
+
+    {
+        let mut icx = unsafe { InterruptContext::new() };
+        assert_eq!(queue.try_send(&mut icx, 0), Ok(()));
+        assert_eq!(queue.try_send(&mut icx, 1), Ok(()));
+        assert_eq!(queue.try_send(&mut icx, 2), Err(2));
+        assert_eq!(queue.try_recv(&mut icx), Some(0));
+    }
+    assert_eq!(queue.try_recv(cx), Some(1));
+
+    let mut task = task::spawn({
+        let queue = queue.clone();
+        move |cx| assert_eq!(queue.recv(cx, BIG_TIMEOUT), Some(3))
+    })
+    .unwrap();
+
+    {
+        let mut icx = unsafe { InterruptContext::new() };
+        assert_eq!(queue.try_send(&mut icx, 3), Ok(()));
+    }
+    task.join(cx, BIG_TIMEOUT).unwrap();
+    assert_eq!(queue.try_recv(cx), None);
+}
+
+#[ustd::attr::test]
+fn event_group(cx: &mut TaskContext) {
+    let group = Arc::new(EventGroup::new().unwrap());
+
+    let mut task = task::spawn({
+        let group = group.clone();
+        move |cx| group.wait_bits(cx, 0b11, WaitMode::All, true, BIG_TIMEOUT)
+    })
+    .unwrap();
+
+    {
+        let mut icx = unsafe { InterruptContext::new() };
+        assert!(group.set_bits(&mut icx, 0b01));
+        assert!(group.set_bits(&mut icx, 0b10));
+    }
+    assert!(task.join(cx, BIG_TIMEOUT).unwrap().is_some());
+    assert_eq!(group.get_bits(cx), 0);
+}
+
+#[ustd::attr::test]
+fn instant(cx: &mut TaskContext) {
+    let before = Instant::now(cx);
+    let start = {
+        let mut icx = unsafe { InterruptContext::new() };
+        Instant::now(&mut icx)
+    };
+    assert!(start >= before);
+
+    cx.sleep(Some(Duration::from_millis(10)));
+    let elapsed = {
+        let mut icx = unsafe { InterruptContext::new() };
+        start.elapsed(&mut icx)
+    };
+    assert!(elapsed >= Duration::from_millis(5));
+    assert!(Instant::now(cx) >= start + elapsed);
+}
@@ -1 +1,4 @@
+pub mod interrupt;
+pub mod sync;
 pub mod tasks;
+pub mod time;
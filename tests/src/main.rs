@@ -1,5 +1,8 @@
+mod interrupt;
+mod sync;
 mod tasks;
+mod time;
 
 use ustd::*;
 
-tests_main![tasks::spawn, tasks::priority, tasks::ping_pong];
+tests_main!();
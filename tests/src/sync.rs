@@ -0,0 +1,378 @@
+extern crate alloc;
+
+use alloc::sync::Arc;
+use core::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use ustd::{
+    sync::{
+        Condvar, CountingSemaphore, EventBits, EventGroup, Mutex, Queue, RwLock, Semaphore,
+        WaitMode,
+    },
+    task::{self, BlockingContext, TaskContext},
+};
+
+const SMALL_TIMEOUT: Option<Duration> = Some(Duration::from_millis(10));
+const BIG_TIMEOUT: Option<Duration> = Some(Duration::from_secs(1));
+
+#[ustd::attr::test]
+fn queue(cx: &mut TaskContext) {
+    const N: usize = 256;
+
+    let queue = Arc::new(Queue::<usize>::new(4).unwrap());
+
+    let mut prod = task::spawn({
+        let queue = queue.clone();
+        move |cx| {
+            for i in 0..N {
+                assert!(queue.send(cx, i, BIG_TIMEOUT).is_ok());
+            }
+        }
+    })
+    .unwrap();
+
+    for i in 0..N {
+        assert_eq!(queue.recv(cx, BIG_TIMEOUT), Some(i));
+    }
+
+    prod.join(cx, BIG_TIMEOUT).unwrap();
+}
+
+#[ustd::attr::test]
+fn queue_bounds(cx: &mut TaskContext) {
+    let queue = Queue::<usize>::new(2).unwrap();
+    assert_eq!(queue.capacity(), 2);
+
+    assert_eq!(queue.try_recv(cx), None);
+    assert_eq!(queue.recv(cx, SMALL_TIMEOUT), None);
+
+    assert_eq!(queue.try_send(cx, 0), Ok(()));
+    assert_eq!(queue.send(cx, 1, SMALL_TIMEOUT), Ok(()));
+    assert_eq!(queue.try_send(cx, 2), Err(2));
+    assert_eq!(queue.send(cx, 3, SMALL_TIMEOUT), Err(3));
+
+    assert_eq!(queue.try_recv(cx), Some(0));
+    assert_eq!(queue.recv(cx, SMALL_TIMEOUT), Some(1));
+    assert_eq!(queue.try_recv(cx), None);
+}
+
+#[ustd::attr::test]
+fn semaphore(cx: &mut TaskContext) {
+    let sem = Semaphore::new().unwrap();
+
+    assert!(!sem.try_take(cx));
+    assert!(!sem.take(cx, SMALL_TIMEOUT));
+
+    assert!(sem.try_give(cx));
+    assert!(!sem.try_give(cx));
+
+    assert!(sem.try_take(cx));
+    assert!(!sem.try_take(cx));
+
+    assert!(sem.try_give(cx));
+    assert!(sem.take(cx, SMALL_TIMEOUT));
+    assert!(!sem.take(cx, SMALL_TIMEOUT));
+}
+
+#[ustd::attr::test]
+fn mutex(cx: &mut TaskContext) {
+    let mutex = Arc::new(Mutex::new(0usize).unwrap());
+    let locked = Arc::new(Semaphore::new().unwrap());
+    let unlock = Arc::new(Semaphore::new().unwrap());
+
+    let mut task = task::spawn({
+        let mutex = mutex.clone();
+        let locked = locked.clone();
+        let unlock = unlock.clone();
+        move |cx| {
+            let mut guard = mutex.lock(cx, None).unwrap();
+            *guard += 1;
+            assert!(locked.try_give(cx));
+            assert!(unlock.take(cx, BIG_TIMEOUT));
+        }
+    })
+    .unwrap();
+
+    assert!(locked.take(cx, BIG_TIMEOUT));
+    assert!(mutex.try_lock(cx).unwrap().is_none());
+    assert!(mutex.lock(cx, SMALL_TIMEOUT).is_err());
+
+    assert!(unlock.try_give(cx));
+    let mut guard = mutex.lock(cx, BIG_TIMEOUT).unwrap();
+    assert_eq!(*guard, 1);
+    *guard += 1;
+    drop(guard);
+    task.join(cx, BIG_TIMEOUT).unwrap();
+
+    assert_eq!(*mutex.try_lock(cx).unwrap().unwrap(), 2);
+}
+
+#[ustd::attr::test]
+fn mutex_exclusive(cx: &mut TaskContext) {
+    use alloc::vec::Vec;
+
+    const TASKS: usize = 4;
+    const N: usize = 64;
+
+    struct Shared {
+        mutex: Mutex<usize>,
+        inside: AtomicUsize,
+    }
+
+    let sh = Arc::new(Shared {
+        mutex: Mutex::new(0).unwrap(),
+        inside: AtomicUsize::new(0),
+    });
+
+    let tasks = (0..TASKS)
+        .map(|_| {
+            let sh = sh.clone();
+            task::spawn(move |cx| {
+                for _ in 0..N {
+                    let mut guard = sh.mutex.lock(cx, None).unwrap();
+                    assert_eq!(sh.inside.fetch_add(1, Ordering::SeqCst), 0);
+                    let value = *guard;
+                    cx.sleep(Some(Duration::ZERO));
+                    *guard = value + 1;
+                    sh.inside.fetch_sub(1, Ordering::SeqCst);
+                }
+            })
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    for mut task in tasks {
+        task.join(cx, BIG_TIMEOUT).unwrap();
+    }
+    assert_eq!(*sh.mutex.lock(cx, None).unwrap(), TASKS * N);
+}
+
+#[ustd::attr::test]
+fn counting_semaphore(cx: &mut TaskContext) {
+    let sem = CountingSemaphore::new(2, 1).unwrap();
+    assert_eq!(sem.max_count(), 2);
+    assert_eq!(sem.count(cx), 1);
+
+    assert!(sem.try_give(cx));
+    assert!(!sem.try_give(cx));
+    assert!(!sem.give(cx, SMALL_TIMEOUT));
+    assert_eq!(sem.count(cx), 2);
+
+    assert!(sem.try_take(cx));
+    assert!(sem.take(cx, SMALL_TIMEOUT));
+    assert!(!sem.try_take(cx));
+    assert!(!sem.take(cx, SMALL_TIMEOUT));
+    assert_eq!(sem.count(cx), 0);
+}
+
+#[ustd::attr::test]
+fn counting_semaphore_pool(cx: &mut TaskContext) {
+    use alloc::vec::Vec;
+
+    const SLOTS: usize = 3;
+    const TASKS: usize = 8;
+
+    struct Shared {
+        sem: CountingSemaphore,
+        used: AtomicUsize,
+    }
+    let sh = Arc::new(Shared {
+        sem: CountingSemaphore::new(SLOTS, SLOTS).unwrap(),
+        used: AtomicUsize::new(0),
+    });
+
+    let tasks = (0..TASKS)
+        .map(|_| {
+            let sh = sh.clone();
+            task::spawn(move |cx| {
+                for _ in 0..16 {
+                    assert!(sh.sem.take(cx, BIG_TIMEOUT));
+                    assert!(sh.used.fetch_add(1, Ordering::SeqCst) < SLOTS);
+                    cx.sleep(Some(Duration::from_millis(1)));
+                    sh.used.fetch_sub(1, Ordering::SeqCst);
+                    assert!(sh.sem.try_give(cx));
+                }
+            })
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    for mut task in tasks {
+        task.join(cx, BIG_TIMEOUT).unwrap();
+    }
+    assert_eq!(sh.sem.count(cx), SLOTS);
+}
+
+#[ustd::attr::test]
+fn huge_timeout(cx: &mut TaskContext) {
+    let sem = Arc::new(Semaphore::new().unwrap());
+
+    let mut task = task::spawn({
+        let sem = sem.clone();
+        move |cx| {
+            cx.sleep(SMALL_TIMEOUT);
+            assert!(sem.try_give(cx));
+        }
+    })
+    .unwrap();
+
+    assert!(sem.take(cx, Some(Duration::MAX)));
+    assert_eq!(task.join(cx, BIG_TIMEOUT), Ok(()));
+}
+
+#[ustd::attr::test]
+fn condvar(cx: &mut TaskContext) {
+    const N: usize = 4;
+
+    struct Shared {
+        counter: Mutex<usize>,
+        condvar: Condvar,
+    }
+    let shared = Arc::new(Shared {
+        counter: Mutex::new(0).unwrap(),
+        condvar: Condvar::new().unwrap(),
+    });
+
+    let tasks = (0..N)
+        .map(|i| {
+            let shared = shared.clone();
+            task::spawn(move |cx| {
+                let guard = shared.counter.lock(cx, None).unwrap();
+                let mut guard = shared
+                    .condvar
+                    .wait_while(cx, guard, |counter| *counter != i)
+                    .unwrap();
+                *guard += 1;
+                drop(guard);
+                shared.condvar.notify_all(cx);
+            })
+            .unwrap()
+        })
+        .collect::<alloc::vec::Vec<_>>();
+
+    let guard = shared.counter.lock(cx, None).unwrap();
+    let guard = shared
+        .condvar
+        .wait_while(cx, guard, |counter| *counter != N)
+        .unwrap();
+    assert_eq!(*guard, N);
+    drop(guard);
+
+    for mut task in tasks {
+        task.join(cx, BIG_TIMEOUT).unwrap();
+    }
+}
+
+#[ustd::attr::test]
+fn condvar_timeout(cx: &mut TaskContext) {
+    let mutex = Arc::new(Mutex::new(false).unwrap());
+    let condvar = Arc::new(Condvar::new().unwrap());
+
+    let guard = mutex.lock(cx, None).unwrap();
+    let (guard, notified) = condvar.wait_timeout(cx, guard, SMALL_TIMEOUT).unwrap();
+    assert!(!notified);
+    assert!(!*guard);
+
+    let mut task = task::spawn({
+        let mutex = mutex.clone();
+        let condvar = condvar.clone();
+        move |cx| {
+            *mutex.lock(cx, None).unwrap() = true;
+            condvar.notify_one(cx);
+        }
+    })
+    .unwrap();
+
+    let (guard, notified) = condvar.wait_timeout(cx, guard, BIG_TIMEOUT).unwrap();
+    assert!(notified);
+    assert!(*guard);
+    drop(guard);
+
+    task.join(cx, BIG_TIMEOUT).unwrap();
+}
+
+#[ustd::attr::test]
+fn rwlock(cx: &mut TaskContext) {
+    let lock = Arc::new(RwLock::new(0).unwrap());
+
+    let first = lock.read(cx, None).unwrap();
+    let second = lock.try_read(cx).unwrap().unwrap();
+    assert_eq!(*first + *second, 0);
+    assert!(lock.try_write(cx).unwrap().is_none());
+
+    let mut writer = task::spawn({
+        let lock = lock.clone();
+        move |cx| *lock.write(cx, BIG_TIMEOUT).unwrap() = 1
+    })
+    .unwrap();
+
+    // Writer is waiting so new readers are blocked.
+    cx.sleep(SMALL_TIMEOUT);
+    assert!(lock.read(cx, SMALL_TIMEOUT).is_err());
+
+    drop((first, second));
+    writer.join(cx, BIG_TIMEOUT).unwrap();
+    assert_eq!(*lock.read(cx, BIG_TIMEOUT).unwrap(), 1);
+
+    let guard = lock.write(cx, None).unwrap();
+    assert!(lock.try_read(cx).unwrap().is_none());
+    assert!(lock.write(cx, SMALL_TIMEOUT).is_err());
+    drop(guard);
+    assert!(lock.try_write(cx).unwrap().is_some());
+}
+
+#[ustd::attr::test]
+fn event_group(cx: &mut TaskContext) {
+    let group = Arc::new(EventGroup::new().unwrap());
+
+    assert_eq!(
+        group.wait_bits(cx, 0b11, WaitMode::Any, false, SMALL_TIMEOUT),
+        None
+    );
+    assert!(group.set_bits(cx, 0b01));
+    assert_eq!(
+        group.wait_bits(cx, 0b11, WaitMode::Any, false, SMALL_TIMEOUT),
+        Some(0b01)
+    );
+    assert_eq!(
+        group.wait_bits(cx, 0b11, WaitMode::All, false, SMALL_TIMEOUT),
+        None
+    );
+
+    let mut task = task::spawn({
+        let group = group.clone();
+        move |cx| group.wait_bits(cx, 0b11, WaitMode::All, true, BIG_TIMEOUT)
+    })
+    .unwrap();
+    cx.sleep(SMALL_TIMEOUT);
+    assert!(group.set_bits(cx, 0b110));
+    assert_eq!(task.join(cx, BIG_TIMEOUT), Ok(Some(0b111)));
+    assert_eq!(group.get_bits(cx), 0b100);
+
+    assert!(group.clear_bits(cx, 0b100));
+    assert_eq!(group.get_bits(cx), 0);
+}
+
+#[ustd::attr::test]
+fn event_group_sync(cx: &mut TaskContext) {
+    const N: usize = 4;
+    const ALL: EventBits = (1 << N) - 1;
+
+    let group = Arc::new(EventGroup::new().unwrap());
+
+    let tasks = (1..N)
+        .map(|i| {
+            let group = group.clone();
+            task::spawn(move |cx| group.sync(cx, 1 << i, ALL, BIG_TIMEOUT)).unwrap()
+        })
+        .collect::<alloc::vec::Vec<_>>();
+
+    cx.sleep(SMALL_TIMEOUT);
+    assert_eq!(group.get_bits(cx), ALL & !1);
+    assert_eq!(group.sync(cx, 1, ALL, BIG_TIMEOUT), Some(ALL));
+    for mut task in tasks {
+        assert_eq!(task.join(cx, BIG_TIMEOUT), Ok(Some(ALL)));
+    }
+    assert_eq!(group.get_bits(cx), 0);
+}
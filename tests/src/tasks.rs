@@ -2,20 +2,20 @@ extern crate alloc;
 
 use alloc::sync::Arc;
 use core::{
+    cell::Cell,
     sync::atomic::{AtomicBool, AtomicUsize, Ordering},
     time::Duration,
 };
-use macro_rules_attribute::apply;
 use ustd::{
     sync::Semaphore,
-    task::{self, BlockingContext, TaskContext},
-    test,
+    task::{self, BlockingContext, JoinError, NotifyAction, TaskContext},
+    task_local,
 };
 
 const SMALL_TIMEOUT: Option<Duration> = Some(Duration::from_millis(10));
 const BIG_TIMEOUT: Option<Duration> = Some(Duration::from_secs(1));
 
-#[apply(test)]
+#[ustd::attr::test]
 fn spawn(cx: &mut TaskContext) {
     struct Shared {
         sem: Semaphore,
@@ -26,7 +26,7 @@ fn spawn(cx: &mut TaskContext) {
         val: AtomicBool::new(false),
     });
 
-    let task = task::spawn({
+    let mut task = task::spawn({
         let sh = sh.clone();
         move |cx| {
             cx.sleep(SMALL_TIMEOUT);
@@ -39,11 +39,11 @@ fn spawn(cx: &mut TaskContext) {
     assert!(sh.sem.take(cx, BIG_TIMEOUT));
     assert!(sh.val.load(Ordering::SeqCst));
 
-    assert!(task.join(cx, BIG_TIMEOUT));
+    task.join(cx, BIG_TIMEOUT).unwrap();
 }
 
-#[cfg(feature = "freertos")]
-#[apply(test)]
+#[cfg(any(feature = "freertos", feature = "single-core"))]
+#[ustd::attr::test(mode = Sequential)]
 fn priority(cx: &mut TaskContext) {
     use alloc::vec::Vec;
     use task::Priority;
@@ -85,12 +85,12 @@ fn priority(cx: &mut TaskContext) {
         sh.val.fetch_add(1, Ordering::SeqCst);
     }
 
-    for h in tasks {
-        assert!(h.join(cx, BIG_TIMEOUT));
+    for mut h in tasks {
+        h.join(cx, BIG_TIMEOUT).unwrap();
     }
 }
 
-#[apply(test)]
+#[ustd::attr::test(timeout = Duration::from_secs(10))]
 fn ping_pong(cx: &mut TaskContext) {
     const N: usize = 1024;
 
@@ -105,7 +105,7 @@ fn ping_pong(cx: &mut TaskContext) {
         val: AtomicUsize::new(0),
     });
 
-    let prod = task::Builder::new()
+    let mut prod = task::Builder::new()
         .spawn({
             let sh = sh.clone();
             move |cx| {
@@ -118,7 +118,7 @@ fn ping_pong(cx: &mut TaskContext) {
         })
         .unwrap();
 
-    let cons = task::Builder::new()
+    let mut cons = task::Builder::new()
         .spawn(move |cx| {
             assert!(sh.isem.try_give(cx));
             for i in 0..N {
@@ -129,6 +129,115 @@ fn ping_pong(cx: &mut TaskContext) {
         })
         .unwrap();
 
-    assert!(prod.join(cx, BIG_TIMEOUT));
-    assert!(cons.join(cx, BIG_TIMEOUT));
+    prod.join(cx, BIG_TIMEOUT).unwrap();
+    cons.join(cx, BIG_TIMEOUT).unwrap();
+}
+
+#[ustd::attr::test]
+fn join(cx: &mut TaskContext) {
+    let sem = Arc::new(Semaphore::new().unwrap());
+
+    let mut task = task::spawn({
+        let sem = sem.clone();
+        move |cx| {
+            assert!(sem.take(cx, BIG_TIMEOUT));
+            42
+        }
+    })
+    .unwrap();
+
+    assert!(!task.wait(cx, SMALL_TIMEOUT));
+    assert!(sem.try_give(cx));
+    assert!(task.wait(cx, BIG_TIMEOUT));
+    assert_eq!(task.join(cx, BIG_TIMEOUT), Ok(42));
+}
+
+#[ustd::attr::test]
+fn join_timeout(cx: &mut TaskContext) {
+    let sem = Arc::new(Semaphore::new().unwrap());
+
+    let mut task = task::spawn({
+        let sem = sem.clone();
+        move |cx| assert!(sem.take(cx, BIG_TIMEOUT))
+    })
+    .unwrap();
+
+    assert_eq!(task.join(cx, SMALL_TIMEOUT), Err(JoinError::Timeout));
+    assert!(sem.try_give(cx));
+    // Handle is still usable after timeout.
+    assert_eq!(task.join(cx, BIG_TIMEOUT), Ok(()));
+}
+
+#[ustd::attr::test]
+fn join_panic(cx: &mut TaskContext) {
+    let mut task = task::spawn(|_| {
+        panic!("Intended panic");
+    })
+    .unwrap();
+
+    assert_eq!(task.join(cx, BIG_TIMEOUT), Err(JoinError::Panicked));
+}
+
+#[ustd::attr::test]
+#[should_panic(expected = "Intended panic")]
+fn should_panic(_cx: &mut TaskContext) {
+    panic!("Intended panic");
+}
+
+#[ustd::attr::test]
+#[ignore = "Must not be run"]
+fn ignore(_cx: &mut TaskContext) {
+    panic!("Ignored test is run");
+}
+
+#[ustd::attr::test]
+fn task_local(cx: &mut TaskContext) {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    struct Counter(Cell<usize>);
+    impl Drop for Counter {
+        fn drop(&mut self) {
+            DROPPED.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+
+    task_local! {
+        static COUNTER: Counter = Counter(Cell::new(0));
+    }
+
+    let count = |cx: &mut TaskContext| {
+        COUNTER.with(cx, |c| c.0.set(c.0.get() + 1));
+        COUNTER.with(cx, |c| c.0.get())
+    };
+    let mut first = task::spawn(move |cx| {
+        assert_eq!(count(cx), 1);
+        // Value of another task isn't affected.
+        let mut second = task::spawn(move |cx| (count(cx), count(cx))).unwrap();
+        assert_eq!(second.join(cx, BIG_TIMEOUT), Ok((1, 2)));
+        assert_eq!(DROPPED.load(Ordering::Acquire), 1);
+        count(cx)
+    })
+    .unwrap();
+    assert_eq!(first.join(cx, BIG_TIMEOUT), Ok(2));
+    assert_eq!(DROPPED.load(Ordering::Acquire), 2);
+}
+
+#[ustd::attr::test]
+fn notify(cx: &mut TaskContext) {
+    let main = cx.task();
+
+    let mut task = task::spawn(move |cx| {
+        cx.sleep(SMALL_TIMEOUT);
+        assert_eq!(cx.wait_notification(u32::MAX, BIG_TIMEOUT), Some(0b101));
+        assert_eq!(cx.wait_notification(0, SMALL_TIMEOUT), None);
+        main.notify(cx, 0, NotifyAction::Increment);
+        assert_eq!(cx.wait_notification(0, BIG_TIMEOUT), Some(7));
+    })
+    .unwrap();
+
+    task.task().notify(cx, 0b001, NotifyAction::SetBits);
+    task.task().notify(cx, 0b100, NotifyAction::SetBits);
+    assert_eq!(cx.wait_notification(0, BIG_TIMEOUT), Some(1));
+    task.task().notify(cx, 7, NotifyAction::Overwrite);
+    task.join(cx, BIG_TIMEOUT).unwrap();
 }
@@ -0,0 +1,243 @@
+extern crate alloc;
+
+use alloc::sync::Arc;
+use core::{
+    ops::ControlFlow,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use ustd::{
+    sync::Semaphore,
+    task::{BlockingContext, TaskContext},
+    time::{self, Instant, Interval, MissedTickBehavior, TimerBuilder},
+};
+
+const SMALL_TIMEOUT: Option<Duration> = Some(Duration::from_millis(10));
+const BIG_TIMEOUT: Option<Duration> = Some(Duration::from_secs(1));
+
+const PERIOD: Duration = Duration::from_millis(10);
+
+#[ustd::attr::test]
+fn instant(cx: &mut TaskContext) {
+    let start = Instant::now(cx);
+    cx.sleep(SMALL_TIMEOUT);
+    let first = start.elapsed(cx);
+    // Allow for tick granularity.
+    assert!(first >= PERIOD / 2);
+    assert!(first < BIG_TIMEOUT.unwrap());
+
+    cx.sleep(SMALL_TIMEOUT);
+    assert!(start.elapsed(cx) >= first);
+    assert!(Instant::now(cx) > start);
+}
+
+/// Duration that can be represented by [`Instant`] exactly.
+fn exact(duration: Duration) -> Duration {
+    #[cfg(feature = "freertos")]
+    {
+        use ustd::time::{duration_to_ticks, ticks_to_duration};
+        ticks_to_duration(duration_to_ticks(duration))
+    }
+    #[cfg(not(feature = "freertos"))]
+    duration
+}
+
+#[ustd::attr::test]
+fn instant_arithmetic(cx: &mut TaskContext) {
+    // Instant is added to duration rounded up to a whole tick.
+    let period = exact(PERIOD);
+    let start = Instant::now(cx);
+    let later = start + PERIOD;
+    assert!(later > start);
+    assert_eq!(later - start, period);
+    assert_eq!(later.duration_since(start), period);
+    assert_eq!(later - PERIOD, start);
+    assert_eq!(start.checked_add(PERIOD), Some(later));
+    assert_eq!(later.checked_sub(PERIOD), Some(start));
+
+    assert_eq!(later.checked_duration_since(start), Some(period));
+    assert_eq!(start.checked_duration_since(later), None);
+    assert_eq!(start.saturating_duration_since(later), Duration::ZERO);
+    assert_eq!(start.duration_since(later), Duration::ZERO);
+
+    let mut instant = start;
+    instant += PERIOD;
+    assert_eq!(instant, later);
+    instant -= PERIOD;
+    assert_eq!(instant, start);
+
+    // Longer than period of 32-bit tick counter at 1 kHz.
+    let long = Duration::from_secs(100 * 24 * 60 * 60);
+    let far = start + long;
+    assert!(far > later);
+    assert_eq!(far - start, exact(long));
+
+    assert!(later.checked_add(Duration::MAX).is_none());
+}
+
+#[cfg(feature = "freertos")]
+#[ustd::attr::test]
+fn tick_conversion(_cx: &mut TaskContext) {
+    use ustd::time::{duration_to_ticks, tick_rate_hz, ticks_to_duration};
+
+    let rate = tick_rate_hz();
+    let tick = Duration::from_secs(1) / rate;
+    assert_eq!(duration_to_ticks(Duration::ZERO), 0);
+    // Rounded up to a whole tick.
+    assert_eq!(duration_to_ticks(Duration::from_nanos(1)), 1);
+    assert_eq!(duration_to_ticks(tick), 1);
+    assert_eq!(duration_to_ticks(tick + Duration::from_nanos(1)), 2);
+    assert_eq!(duration_to_ticks(Duration::from_secs(1)), rate as u64);
+    assert_eq!(duration_to_ticks(Duration::MAX), u64::MAX);
+
+    assert_eq!(ticks_to_duration(0), Duration::ZERO);
+    assert_eq!(ticks_to_duration(rate as u64), Duration::from_secs(1));
+    assert_eq!(duration_to_ticks(ticks_to_duration(12345)), 12345);
+}
+
+#[ustd::attr::test]
+fn timer(cx: &mut TaskContext) {
+    const N: usize = 3;
+
+    let count = Arc::new(AtomicUsize::new(0));
+    let done = Arc::new(Semaphore::new().unwrap());
+
+    let _timer = TimerBuilder::new(PERIOD)
+        .name("timer")
+        .spawn({
+            let count = count.clone();
+            let done = done.clone();
+            move |cx| {
+                if count.fetch_add(1, Ordering::SeqCst) + 1 < N {
+                    ControlFlow::Continue(None)
+                } else {
+                    assert!(done.try_give(cx));
+                    ControlFlow::Break(())
+                }
+            }
+        })
+        .unwrap();
+
+    assert!(done.take(cx, BIG_TIMEOUT));
+    cx.sleep(Some(4 * PERIOD));
+    assert_eq!(count.load(Ordering::SeqCst), N);
+}
+
+#[ustd::attr::test]
+fn timer_stop(cx: &mut TaskContext) {
+    let count = Arc::new(AtomicUsize::new(0));
+    let fired = Arc::new(Semaphore::new().unwrap());
+
+    let timer = TimerBuilder::new(PERIOD)
+        .spawn({
+            let count = count.clone();
+            let fired = fired.clone();
+            move |cx| {
+                count.fetch_add(1, Ordering::SeqCst);
+                fired.try_give(cx);
+                ControlFlow::Continue(None)
+            }
+        })
+        .unwrap();
+
+    assert!(fired.take(cx, BIG_TIMEOUT));
+    timer.stop();
+    // Callback may already be running when the timer is stopped.
+    cx.sleep(Some(2 * PERIOD));
+    let stopped = count.load(Ordering::SeqCst);
+    cx.sleep(Some(4 * PERIOD));
+    assert_eq!(count.load(Ordering::SeqCst), stopped);
+}
+
+#[ustd::attr::test]
+fn timer_period(cx: &mut TaskContext) {
+    let fired = Arc::new(Semaphore::new().unwrap());
+
+    let timer = TimerBuilder::new(PERIOD)
+        .spawn({
+            let fired = fired.clone();
+            move |cx| {
+                fired.try_give(cx);
+                ControlFlow::Continue(Some(Duration::from_secs(10)))
+            }
+        })
+        .unwrap();
+
+    assert!(fired.take(cx, BIG_TIMEOUT));
+    assert!(!fired.take(cx, Some(4 * PERIOD)));
+    timer.stop();
+}
+
+#[ustd::attr::test]
+fn sleep_until(cx: &mut TaskContext) {
+    let deadline = Instant::now(cx) + PERIOD;
+    time::sleep_until(cx, deadline);
+    assert!(Instant::now(cx) >= deadline);
+
+    // Deadline has passed.
+    time::sleep_until(cx, deadline);
+}
+
+#[ustd::attr::test(mode = Sequential)]
+fn interval(cx: &mut TaskContext) {
+    const N: u32 = 5;
+    const PERIOD: Duration = Duration::from_millis(20);
+
+    let start = Instant::now(cx);
+    let mut interval = Interval::new(cx, PERIOD);
+    assert_eq!(interval.period(), PERIOD);
+    assert_eq!(interval.missed_tick_behavior(), MissedTickBehavior::Burst);
+    for _ in 0..N {
+        assert_eq!(interval.tick(cx), 0);
+        // Body execution time must not accumulate.
+        cx.sleep(Some(3 * PERIOD / 4));
+    }
+    let elapsed = start.elapsed(cx);
+    assert!(elapsed >= (N - 1) * PERIOD);
+    assert!(elapsed < (N + 1) * PERIOD);
+}
+
+/// Interval which first tick is late by at least two and a half periods.
+fn late_interval(cx: &mut TaskContext, behavior: MissedTickBehavior) -> (Instant, Interval) {
+    let start = Instant::now(cx);
+    let mut interval = Interval::new_at(start, PERIOD);
+    interval.set_missed_tick_behavior(behavior);
+    cx.sleep(Some(5 * PERIOD / 2));
+    (start, interval)
+}
+
+#[ustd::attr::test]
+fn interval_burst(cx: &mut TaskContext) {
+    let (start, mut interval) = late_interval(cx, MissedTickBehavior::Burst);
+    assert!(interval.tick(cx) >= 2);
+    // Missed tick fires immediately.
+    assert!(interval.tick(cx) >= 1);
+    assert!(start.elapsed(cx) < BIG_TIMEOUT.unwrap());
+}
+
+#[ustd::attr::test]
+fn interval_delay(cx: &mut TaskContext) {
+    let (_, mut interval) = late_interval(cx, MissedTickBehavior::Delay);
+    assert!(interval.tick(cx) >= 2);
+    let late = Instant::now(cx);
+    interval.tick(cx);
+    assert!(Instant::now(cx) >= late + PERIOD);
+}
+
+#[ustd::attr::test]
+fn interval_skip(cx: &mut TaskContext) {
+    let (start, mut interval) = late_interval(cx, MissedTickBehavior::Skip);
+    let missed = interval.tick(cx);
+    assert!(missed >= 2);
+    interval.tick(cx);
+    assert!(Instant::now(cx) >= start + (missed + 1) * PERIOD);
+}
+
+#[ustd::attr::test]
+fn interval_reset(cx: &mut TaskContext) {
+    let (_, mut interval) = late_interval(cx, MissedTickBehavior::Burst);
+    interval.reset(cx);
+    let reset = Instant::now(cx);
+    assert_eq!(interval.tick(cx), 0);
+    assert!(Instant::now(cx) >= reset + PERIOD / 2);
+}